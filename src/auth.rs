@@ -0,0 +1,190 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+use http::StatusCode;
+
+use crate::{
+    config::{ApiKey, Quota},
+    metrics::API_KEY_REQUESTS,
+};
+
+/// Header carrying the API key of a request, unless it is given in the path.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("missing API key")]
+    MissingKey,
+
+    #[error("invalid API key")]
+    InvalidKey,
+
+    #[error("API key is not allowed to forward requests to chain-id {0}")]
+    ChainNotAllowed(u16),
+
+    #[error("API key quota of {requests} requests per {period_secs} seconds exceeded")]
+    QuotaExceeded { requests: u64, period_secs: u64 },
+}
+
+impl AuthError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::MissingKey | Self::InvalidKey => StatusCode::UNAUTHORIZED,
+            Self::ChainNotAllowed(_) => StatusCode::FORBIDDEN,
+            Self::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct KeyState {
+    config: ApiKey,
+    /// Start of the current quota period and the requests forwarded within it.
+    period: Mutex<(Instant, u64)>,
+}
+
+impl KeyState {
+    /// Counts a request against the quota of the key, if any.
+    fn consume(&self, quota: Option<Quota>, now: Instant) -> Result<(), AuthError> {
+        let mut period = self.period.lock().unwrap();
+        if let Some(quota) = quota {
+            if now.duration_since(period.0) >= Duration::from_secs(quota.period_secs) {
+                *period = (now, 0);
+            }
+            if period.1 >= quota.requests {
+                return Err(AuthError::QuotaExceeded {
+                    requests: quota.requests,
+                    period_secs: quota.period_secs,
+                });
+            }
+        }
+        period.1 += 1;
+        Ok(())
+    }
+}
+
+/// `ApiKeys` authenticates the requests forwarded to preconfers and enforces the chains and
+/// quotas of their API key. Every request is allowed if no key is configured. The usage of each
+/// key is counted in the `api_key_requests_total` metric.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    keys: HashMap<String, KeyState>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        let now = Instant::now();
+        let keys = keys
+            .into_iter()
+            .map(|config| {
+                let state = KeyState { config: config.clone(), period: Mutex::new((now, 0)) };
+                (config.key, state)
+            })
+            .collect();
+        Self { keys }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Checks that `key` is allowed to access `chain_id`, without counting a request against its
+    /// quota.
+    pub fn check(&self, key: Option<&str>, chain_id: u16) -> Result<(), AuthError> {
+        self.key_state(key, chain_id).map(|_| ())
+    }
+
+    /// Authorizes a request to `chain_id` with `key` and counts it in the usage of the key.
+    pub fn authorize(
+        &self,
+        key: Option<&str>,
+        chain_id: u16,
+        now: Instant,
+    ) -> Result<(), AuthError> {
+        let Some(state) = self.key_state(key, chain_id)? else { return Ok(()) };
+        let name = state.config.name.as_str();
+        if let Err(err) = state.consume(state.config.quota, now) {
+            API_KEY_REQUESTS.with_label_values(&[name, "quota_exceeded"]).inc();
+            return Err(err);
+        }
+        API_KEY_REQUESTS.with_label_values(&[name, "ok"]).inc();
+        Ok(())
+    }
+
+    /// Returns the state of `key` if it is allowed to access `chain_id`, or `None` if no key is
+    /// configured.
+    fn key_state(&self, key: Option<&str>, chain_id: u16) -> Result<Option<&KeyState>, AuthError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let key = key.ok_or(AuthError::MissingKey)?;
+        let state = self.keys.get(key).ok_or(AuthError::InvalidKey)?;
+
+        let allowed = state.config.allowed_chain_ids.as_ref();
+        if allowed.is_some_and(|chain_ids| !chain_ids.contains(&chain_id)) {
+            API_KEY_REQUESTS.with_label_values(&[&state.config.name, "forbidden"]).inc();
+            return Err(AuthError::ChainNotAllowed(chain_id));
+        }
+        Ok(Some(state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_keys() -> ApiKeys {
+        ApiKeys::new(vec![ApiKey {
+            name: "partner".into(),
+            key: "secret".into(),
+            allowed_chain_ids: Some(vec![1]),
+            quota: Some(Quota { requests: 2, period_secs: 60 }),
+        }])
+    }
+
+    #[test]
+    fn test_disabled_without_keys() {
+        ApiKeys::default().authorize(None, 1, Instant::now()).unwrap();
+    }
+
+    #[test]
+    fn test_authorize() {
+        let keys = api_keys();
+        let now = Instant::now();
+        assert!(matches!(keys.authorize(None, 1, now), Err(AuthError::MissingKey)));
+        assert!(matches!(keys.authorize(Some("wrong"), 1, now), Err(AuthError::InvalidKey)));
+        assert!(matches!(
+            keys.authorize(Some("secret"), 2, now),
+            Err(AuthError::ChainNotAllowed(2))
+        ));
+
+        keys.authorize(Some("secret"), 1, now).unwrap();
+        keys.authorize(Some("secret"), 1, now).unwrap();
+        let err = keys.authorize(Some("secret"), 1, now).unwrap_err();
+        assert_eq!(err.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // the quota is reset every period.
+        keys.authorize(Some("secret"), 1, now + Duration::from_secs(60)).unwrap();
+        assert_eq!(API_KEY_REQUESTS.with_label_values(&["partner", "ok"]).get(), 3);
+        assert_eq!(API_KEY_REQUESTS.with_label_values(&["partner", "quota_exceeded"]).get(), 1);
+    }
+
+    #[test]
+    fn test_check_does_not_count() {
+        let keys = ApiKeys::new(vec![ApiKey {
+            name: "subscriber".into(),
+            key: "secret".into(),
+            allowed_chain_ids: Some(vec![1]),
+            quota: Some(Quota { requests: 1, period_secs: 60 }),
+        }]);
+        assert!(matches!(keys.check(None, 1), Err(AuthError::MissingKey)));
+        assert!(matches!(keys.check(Some("secret"), 2), Err(AuthError::ChainNotAllowed(2))));
+        keys.check(Some("secret"), 1).unwrap();
+        keys.check(Some("secret"), 1).unwrap();
+        keys.authorize(Some("secret"), 1, Instant::now()).unwrap();
+        ApiKeys::default().check(None, 1).unwrap();
+    }
+}
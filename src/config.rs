@@ -1,11 +1,22 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use alloy::rpc::types::beacon::BlsPublicKey;
 use eyre::{Result, WrapErr};
 use hashbrown::HashMap;
+use http::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Deserializer};
 use url::Url;
 
+use crate::{
+    auth::API_KEY_HEADER,
+    constants::{EPOCH_SLOTS, SECONDS_PER_SLOT},
+};
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Provider {
@@ -19,14 +30,404 @@ pub struct Config {
     pub lookaheads: Vec<Lookahead>,
     #[serde(rename = "beacon-nodes")]
     pub beacon_nodes: Vec<String>,
+    /// API keys allowed to forward requests. Requests are not authenticated if empty.
+    #[serde(rename = "api-keys", default)]
+    pub api_keys: Vec<ApiKey>,
+    /// Rate limits of the forwarded requests. Requests are not rate limited if not set.
+    #[serde(rename = "rate-limit")]
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Configuration of a single relay supporting the constraints API.
+#[derive(Debug, Clone)]
+pub struct Relay {
+    pub url: String,
+    /// Name of the relay in metrics, the host of its url by default.
+    pub name: String,
+    /// True if the relay supports fetching all elected preconfers in a single call.
+    pub get_lookahead_enabled: bool,
+    /// True if the relay streams new preconfer elections. Polling is kept as a fallback.
+    pub subscribe_enabled: bool,
+    /// Timeout of every request sent to the relay, in milliseconds.
+    pub timeout_ms: u64,
+    /// Custom headers sent with every request to the relay. The `api-key`, if any, is sent in the
+    /// `X-API-Key` header.
+    pub headers: HeaderMap,
+    /// Elections from relays with a higher weight take precedence when relays disagree.
+    pub weight: u32,
+}
+
+impl Relay {
+    pub const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+    pub const DEFAULT_WEIGHT: u32 = 1;
+
+    /// Relay with default settings, used when the relay is configured by its url only.
+    pub fn from_url(url: String) -> Self {
+        let name = Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(ToOwned::to_owned))
+            .unwrap_or_else(|| url.clone());
+        Self {
+            url,
+            name,
+            get_lookahead_enabled: true,
+            subscribe_enabled: false,
+            timeout_ms: Self::DEFAULT_TIMEOUT_MS,
+            headers: HeaderMap::new(),
+            weight: Self::DEFAULT_WEIGHT,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Relay {
+    fn deserialize<D>(deserializer: D) -> Result<Relay, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct RelayTable {
+            url: String,
+            name: Option<String>,
+            get_lookahead_enabled: Option<bool>,
+            subscribe_enabled: Option<bool>,
+            timeout_ms: Option<u64>,
+            #[serde(default)]
+            headers: HashMap<String, String>,
+            api_key: Option<String>,
+            weight: Option<u32>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RelayHelper {
+            Url(String),
+            Table(RelayTable),
+        }
+
+        let table = match RelayHelper::deserialize(deserializer)? {
+            RelayHelper::Url(url) => return Ok(Relay::from_url(url)),
+            RelayHelper::Table(table) => table,
+        };
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in table.headers {
+            let name = HeaderName::from_str(&name).map_err(serde::de::Error::custom)?;
+            let value = HeaderValue::from_str(&value).map_err(serde::de::Error::custom)?;
+            headers.insert(name, value);
+        }
+        if let Some(api_key) = table.api_key {
+            let mut value = HeaderValue::from_str(&api_key).map_err(serde::de::Error::custom)?;
+            value.set_sensitive(true);
+            headers.insert(API_KEY_HEADER, value);
+        }
+
+        let defaults = Relay::from_url(table.url);
+        Ok(Relay {
+            get_lookahead_enabled: table
+                .get_lookahead_enabled
+                .unwrap_or(defaults.get_lookahead_enabled),
+            subscribe_enabled: table.subscribe_enabled.unwrap_or(defaults.subscribe_enabled),
+            timeout_ms: table.timeout_ms.unwrap_or(defaults.timeout_ms),
+            headers,
+            weight: table.weight.unwrap_or(defaults.weight),
+            name: table.name.unwrap_or(defaults.name),
+            ..defaults
+        })
+    }
+}
+
+/// Defines when the lookahead is fetched from the relays.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct FetchSchedule {
+    /// Slots into the epoch at which the lookahead is fetched. Often when querying duties on the
+    /// epoch boundary the values are incorrect, so the default waits one slot.
+    pub slots_into_epoch: Vec<u64>,
+    /// If set, the lookahead is fetched again every `refetch-interval` slots after a fetch.
+    pub refetch_interval: Option<u64>,
+    /// Whether to fetch the lookahead of the current epoch.
+    pub fetch_current_epoch: bool,
+    /// Whether to fetch the lookahead of the next epoch.
+    pub fetch_next_epoch: bool,
+}
+
+impl Default for FetchSchedule {
+    fn default() -> Self {
+        Self {
+            slots_into_epoch: vec![1],
+            refetch_interval: None,
+            fetch_current_epoch: false,
+            fetch_next_epoch: true,
+        }
+    }
+}
+
+/// Behaviour of the forwarder when no preconfer is elected in the lookahead.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Fallback {
+    /// Forwards requests as is to a default gateway or preconfer.
+    Gateway { url: Url },
+    /// Sends the transaction of inclusion requests to a public mempool RPC as a plain
+    /// `eth_sendRawTransaction`. Other requests are forwarded as is.
+    Mempool { url: Url },
+    /// Queues requests until a preconfer is elected for their slot. Requests not forwarded
+    /// within `timeout-ms` are answered with a ticket.
+    #[serde(rename_all = "kebab-case")]
+    Queue {
+        #[serde(default = "Fallback::default_queue_timeout_ms")]
+        timeout_ms: u64,
+    },
+}
+
+impl Fallback {
+    /// Requests wait for at most one slot to be forwarded by default.
+    pub const DEFAULT_QUEUE_TIMEOUT_MS: u64 = 12_000;
+
+    fn default_queue_timeout_ms() -> u64 {
+        Self::DEFAULT_QUEUE_TIMEOUT_MS
+    }
+}
+
+/// What to do with requests arriving after the cutoff of a `Deadline`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeadlineAction {
+    /// Rejects the request.
+    #[default]
+    Reject,
+    /// Forwards the request to the next elected preconfer, unless the cutoff before its block
+    /// proposal has passed too.
+    Reroute,
+}
+
+/// Cutoff before the block proposal of a slot after which requests are not forwarded to the
+/// preconfer elected for that slot anymore, as they could not be honored.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Deadline {
+    /// Unix timestamp of the beacon chain genesis, in seconds.
+    pub genesis_time: u64,
+    #[serde(default = "Deadline::default_seconds_per_slot")]
+    pub seconds_per_slot: u64,
+    /// Requests arriving less than `cutoff-ms` before the block proposal are not forwarded.
+    pub cutoff_ms: u64,
+    #[serde(default)]
+    pub action: DeadlineAction,
+}
+
+impl Deadline {
+    fn default_seconds_per_slot() -> u64 {
+        SECONDS_PER_SLOT
+    }
+
+    /// Unix timestamp in milliseconds at which the block of `slot` is proposed.
+    pub fn slot_start_ms(&self, slot: u64) -> u64 {
+        (self.genesis_time + slot * self.seconds_per_slot) * 1000
+    }
+
+    /// Returns true if `now_ms` is within the cutoff before the block proposal of `slot`, or
+    /// after it.
+    pub fn is_past_cutoff(&self, slot: u64, now_ms: u64) -> bool {
+        now_ms + self.cutoff_ms >= self.slot_start_ms(slot)
+    }
+}
+
+/// Execution node the transactions of inclusion requests are validated against.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Validation {
+    pub execution_rpc: Url,
+    #[serde(default = "Validation::default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl Validation {
+    pub const DEFAULT_TIMEOUT_MS: u64 = 1_000;
+
+    fn default_timeout_ms() -> u64 {
+        Self::DEFAULT_TIMEOUT_MS
+    }
+}
+
+/// HTTP client the requests of a chain are forwarded to preconfers with.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ForwardClient {
+    #[serde(flatten)]
+    pub settings: ClientSettings,
+    /// Settings of the clients of specific preconfers, keyed by url. Unset settings are the
+    /// ones of the chain.
+    #[serde(default)]
+    pub preconfers: HashMap<Url, ClientSettings>,
+    /// Connections to the preconfers elected in the next `prewarm-slots` slots are opened on
+    /// every new head. Disabled if 0.
+    #[serde(default = "ForwardClient::default_prewarm_slots")]
+    pub prewarm_slots: u64,
+}
+
+impl ForwardClient {
+    pub const DEFAULT_PREWARM_SLOTS: u64 = 2;
+
+    fn default_prewarm_slots() -> u64 {
+        Self::DEFAULT_PREWARM_SLOTS
+    }
+}
+
+impl Default for ForwardClient {
+    fn default() -> Self {
+        Self {
+            settings: ClientSettings::default(),
+            preconfers: HashMap::new(),
+            prewarm_slots: Self::DEFAULT_PREWARM_SLOTS,
+        }
+    }
+}
+
+/// Settings of an HTTP client. The defaults of `reqwest` apply to unset settings, except the
+/// timeout.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClientSettings {
+    /// Timeout of every request, in milliseconds.
+    pub timeout_ms: Option<u64>,
+    /// Uses HTTP/2 without negotiating it first, for preconfers that only speak HTTP/2.
+    pub http2_prior_knowledge: Option<bool>,
+    /// Maximum number of idle connections kept per host.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Idle connections are closed after `pool-idle-timeout-ms`.
+    pub pool_idle_timeout_ms: Option<u64>,
+    /// Interval of the TCP keep-alive probes, disabled if not set.
+    pub tcp_keepalive_ms: Option<u64>,
+    pub tcp_nodelay: Option<bool>,
+}
+
+impl ClientSettings {
+    pub const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+    /// Returns these settings, with the unset ones taken from `defaults`.
+    pub fn or(&self, defaults: &ClientSettings) -> ClientSettings {
+        ClientSettings {
+            timeout_ms: self.timeout_ms.or(defaults.timeout_ms),
+            http2_prior_knowledge: self.http2_prior_knowledge.or(defaults.http2_prior_knowledge),
+            pool_max_idle_per_host: self.pool_max_idle_per_host.or(defaults.pool_max_idle_per_host),
+            pool_idle_timeout_ms: self.pool_idle_timeout_ms.or(defaults.pool_idle_timeout_ms),
+            tcp_keepalive_ms: self.tcp_keepalive_ms.or(defaults.tcp_keepalive_ms),
+            tcp_nodelay: self.tcp_nodelay.or(defaults.tcp_nodelay),
+        }
+    }
+}
+
+/// Health probe of the preconfers elected next. Preconfers whose health endpoint cannot be
+/// reached are skipped in favor of the next reachable preconfer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HealthProbe {
+    /// Path of the health endpoint of the preconfers.
+    #[serde(default = "HealthProbe::default_path")]
+    pub path: String,
+    /// Number of elected preconfers probed, from the head slot.
+    #[serde(default = "HealthProbe::default_entries")]
+    pub entries: usize,
+    #[serde(default = "HealthProbe::default_interval_ms")]
+    pub interval_ms: u64,
+    #[serde(default = "HealthProbe::default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl HealthProbe {
+    pub const DEFAULT_PATH: &'static str = "/health";
+    pub const DEFAULT_ENTRIES: usize = 4;
+    pub const DEFAULT_INTERVAL_MS: u64 = 2_000;
+    pub const DEFAULT_TIMEOUT_MS: u64 = 500;
+
+    fn default_path() -> String {
+        Self::DEFAULT_PATH.to_string()
+    }
+
+    fn default_entries() -> usize {
+        Self::DEFAULT_ENTRIES
+    }
+
+    fn default_interval_ms() -> u64 {
+        Self::DEFAULT_INTERVAL_MS
+    }
+
+    fn default_timeout_ms() -> u64 {
+        Self::DEFAULT_TIMEOUT_MS
+    }
+}
+
+/// API key of a client of the forward service.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ApiKey {
+    /// Name of the client, used to label the usage metrics instead of the key.
+    pub name: String,
+    pub key: String,
+    /// Chains the key can forward requests to. Any chain if not set.
+    pub allowed_chain_ids: Option<Vec<u16>>,
+    /// Maximum number of requests forwarded with the key. Unlimited if not set.
+    pub quota: Option<Quota>,
+}
+
+/// Maximum number of requests per period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Quota {
+    pub requests: u64,
+    #[serde(default = "Quota::default_period_secs")]
+    pub period_secs: u64,
+}
+
+impl Quota {
+    pub const DEFAULT_PERIOD_SECS: u64 = 60;
+
+    fn default_period_secs() -> u64 {
+        Self::DEFAULT_PERIOD_SECS
+    }
+}
+
+/// Rate limits of the forwarded requests by client IP and by transaction sender.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RateLimit {
+    /// Proxies whose `X-Forwarded-For` header is trusted to find the IP of the client.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    pub per_ip: Option<TokenBucket>,
+    /// Limit by sender of the transactions of inclusion requests.
+    pub per_sender: Option<TokenBucket>,
+}
+
+/// Token bucket of `capacity` requests, refilled with `refill-per-sec` requests every second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TokenBucket {
+    pub capacity: u64,
+    pub refill_per_sec: u64,
 }
 
 #[derive(Debug)]
 pub struct Lookahead {
     pub chain_id: u16,
-    pub relays: Vec<String>,
+    pub relays: Vec<Relay>,
     pub registry: Option<HashMap<BlsPublicKey, Url>>,
     pub provider: Provider,
+    pub schedule: FetchSchedule,
+    /// Static schedule file the lookahead is read from instead of the relays.
+    pub lookahead_file: Option<PathBuf>,
+    /// Behaviour when no preconfer is elected. Requests fail if not set.
+    pub fallback: Option<Fallback>,
+    /// Cutoff before the block proposal after which requests are rejected or rerouted.
+    pub deadline: Option<Deadline>,
+    /// Validates the transactions of inclusion requests before forwarding them.
+    pub validation: Option<Validation>,
+    /// HTTP client the requests are forwarded to preconfers with.
+    pub client: ForwardClient,
+    /// Probes the health of the next elected preconfers to fail over from unreachable ones.
+    pub health_probe: Option<HealthProbe>,
 }
 
 impl<'de> Deserialize<'de> for Lookahead {
@@ -38,9 +439,19 @@ impl<'de> Deserialize<'de> for Lookahead {
         #[serde(rename_all = "kebab-case")]
         struct LookaheadHelper {
             chain_id: u16,
-            relays: Vec<String>,
+            #[serde(default)]
+            relays: Vec<Relay>,
             registry: Option<HashMap<BlsPublicKey, Url>>,
             url_provider: Provider,
+            #[serde(default)]
+            schedule: FetchSchedule,
+            lookahead_file: Option<PathBuf>,
+            fallback: Option<Fallback>,
+            deadline: Option<Deadline>,
+            validation: Option<Validation>,
+            #[serde(default)]
+            client: ForwardClient,
+            health_probe: Option<HealthProbe>,
         }
 
         let helper = LookaheadHelper::deserialize(deserializer)?;
@@ -51,11 +462,49 @@ impl<'de> Deserialize<'de> for Lookahead {
             ));
         }
 
+        if helper.lookahead_file.is_some() && !helper.relays.is_empty() {
+            return Err(serde::de::Error::custom(
+                "relays cannot be set when the lookahead is read from a lookahead-file",
+            ));
+        }
+
+        if helper.health_probe.as_ref().is_some_and(|probe| probe.interval_ms == 0) {
+            return Err(serde::de::Error::custom("health-probe interval-ms must be positive"));
+        }
+
+        if helper.deadline.as_ref().is_some_and(|deadline| deadline.seconds_per_slot == 0) {
+            return Err(serde::de::Error::custom("deadline seconds-per-slot must be positive"));
+        }
+
+        let mut schedule = helper.schedule;
+        if schedule.slots_into_epoch.iter().any(|slot| *slot >= EPOCH_SLOTS) {
+            return Err(serde::de::Error::custom(format!(
+                "schedule slots-into-epoch must be lower than {EPOCH_SLOTS}"
+            )));
+        }
+        if !schedule.fetch_current_epoch && !schedule.fetch_next_epoch {
+            return Err(serde::de::Error::custom(
+                "schedule must fetch at least one of the current and next epoch",
+            ));
+        }
+        if schedule.refetch_interval == Some(0) {
+            return Err(serde::de::Error::custom("schedule refetch-interval must be positive"));
+        }
+        schedule.slots_into_epoch.sort_unstable();
+        schedule.slots_into_epoch.dedup();
+
         Ok(Lookahead {
             chain_id: helper.chain_id,
             relays: helper.relays,
             registry: helper.registry,
             provider: helper.url_provider,
+            schedule,
+            lookahead_file: helper.lookahead_file,
+            fallback: helper.fallback,
+            deadline: helper.deadline,
+            validation: helper.validation,
+            client: helper.client,
+            health_probe: helper.health_probe,
         })
     }
 }
@@ -93,14 +542,26 @@ mod tests {
 
         let expected_lookahead = Lookahead {
             chain_id: 1,
-            relays: vec!["relay1".to_string(), "relay2".to_string()],
+            relays: vec![
+                Relay::from_url("relay1".to_string()),
+                Relay::from_url("relay2".to_string()),
+            ],
             registry: Some(expected_registry),
             provider: Provider::Lookahead,
+            schedule: FetchSchedule::default(),
+            lookahead_file: None,
+            fallback: None,
+            deadline: None,
+            validation: None,
+            client: ForwardClient::default(),
+            health_probe: None,
         };
 
         let _expected_config = Config {
             lookaheads: vec![expected_lookahead],
             beacon_nodes: vec!["node1".to_string(), "node2".to_string()],
+            api_keys: vec![],
+            rate_limit: None,
         };
 
         let config: Config = toml::from_str(data).unwrap();
@@ -119,20 +580,404 @@ mod tests {
 
         let expected_lookahead = Lookahead {
             chain_id: 1,
-            relays: vec!["relay1".to_string(), "relay2".to_string()],
+            relays: vec![
+                Relay::from_url("relay1".to_string()),
+                Relay::from_url("relay2".to_string()),
+            ],
             registry: None,
             provider: Provider::Lookahead,
+            schedule: FetchSchedule::default(),
+            lookahead_file: None,
+            fallback: None,
+            deadline: None,
+            validation: None,
+            client: ForwardClient::default(),
+            health_probe: None,
         };
 
         let _expected_config = Config {
             lookaheads: vec![expected_lookahead],
             beacon_nodes: vec!["node1".to_string(), "node2".to_string()],
+            api_keys: vec![],
+            rate_limit: None,
         };
 
         let config: Config = toml::from_str(data).unwrap();
         assert!(matches!(config, _expected_config));
     }
 
+    #[test]
+    fn test_deserialize_relay_tables() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        [[lookahead]]
+        chain-id = 2
+        url-provider = "lookahead"
+        [[lookahead.relays]]
+        url = "relay2"
+        get-lookahead-enabled = false
+        subscribe-enabled = true
+        timeout-ms = 1000
+        api-key = "secret"
+        weight = 10
+        [lookahead.relays.headers]
+        x-custom-header = "custom"
+        [[lookahead.relays]]
+        url = "relay3"
+        "#;
+
+        let config: Config = toml::from_str(data).unwrap();
+
+        let relay = &config.lookaheads[0].relays[0];
+        assert_eq!(relay.url, "relay1");
+        assert!(relay.get_lookahead_enabled);
+        assert!(!relay.subscribe_enabled);
+        assert_eq!(relay.timeout_ms, Relay::DEFAULT_TIMEOUT_MS);
+        assert_eq!(relay.weight, Relay::DEFAULT_WEIGHT);
+
+        let relay = &config.lookaheads[1].relays[0];
+        assert_eq!(relay.url, "relay2");
+        assert!(!relay.get_lookahead_enabled);
+        assert!(relay.subscribe_enabled);
+        assert_eq!(relay.timeout_ms, 1000);
+        assert_eq!(relay.headers.get(API_KEY_HEADER).unwrap(), "secret");
+        assert!(relay.headers.get(API_KEY_HEADER).unwrap().is_sensitive());
+        assert_eq!(relay.weight, 10);
+        assert_eq!(relay.headers.get("x-custom-header").unwrap(), "custom");
+
+        let relay = &config.lookaheads[1].relays[1];
+        assert_eq!(relay.url, "relay3");
+        assert!(relay.get_lookahead_enabled);
+        assert!(relay.headers.is_empty());
+    }
+
+    #[test]
+    fn test_relay_name() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = [
+            "https://relay1.example:8080/path",
+            "relay2",
+            { url = "https://relay3.example", name = "relay3" },
+        ]
+        "#;
+
+        let config: Config = toml::from_str(data).unwrap();
+        let names: Vec<_> =
+            config.lookaheads[0].relays.iter().map(|relay| relay.name.as_str()).collect();
+        assert_eq!(names, vec!["relay1.example", "relay2", "relay3"]);
+    }
+
+    #[test]
+    fn test_fail_if_invalid_relay_header() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        [[lookahead.relays]]
+        url = "relay1"
+        [lookahead.relays.headers]
+        "invalid header" = "value"
+        "#;
+        let config: Result<Config> = toml::from_str(data).wrap_err("error parsing config");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_schedule() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        [[lookahead]]
+        chain-id = 2
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        [lookahead.schedule]
+        slots-into-epoch = [16, 2, 16]
+        refetch-interval = 4
+        fetch-current-epoch = true
+        "#;
+
+        let config: Config = toml::from_str(data).unwrap();
+        assert_eq!(config.lookaheads[0].schedule, FetchSchedule::default());
+        assert_eq!(config.lookaheads[1].schedule, FetchSchedule {
+            slots_into_epoch: vec![2, 16],
+            refetch_interval: Some(4),
+            fetch_current_epoch: true,
+            fetch_next_epoch: true,
+        });
+    }
+
+    #[test]
+    fn test_fail_if_schedule_slot_out_of_epoch() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        [lookahead.schedule]
+        slots-into-epoch = [32]
+        "#;
+        let config: Result<Config> = toml::from_str(data).wrap_err("error parsing config");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_lookahead_file() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        lookahead-file = "schedule.toml"
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        assert_eq!(config.lookaheads[0].lookahead_file, Some(PathBuf::from("schedule.toml")));
+        assert!(config.lookaheads[0].relays.is_empty());
+
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        lookahead-file = "schedule.toml"
+        relays = ["relay1"]
+        "#;
+        let config: Result<Config> = toml::from_str(data).wrap_err("error parsing config");
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_fallback() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        fallback = { type = "gateway", url = "http://gateway" }
+        [[lookahead]]
+        chain-id = 2
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        fallback = { type = "mempool", url = "http://mempool" }
+        [[lookahead]]
+        chain-id = 3
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        fallback = { type = "queue" }
+        [[lookahead]]
+        chain-id = 4
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        [lookahead.fallback]
+        type = "queue"
+        timeout-ms = 500
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        let fallbacks: Vec<_> = config.lookaheads.into_iter().map(|l| l.fallback).collect();
+        assert_eq!(fallbacks, vec![
+            Some(Fallback::Gateway { url: Url::from_str("http://gateway").unwrap() }),
+            Some(Fallback::Mempool { url: Url::from_str("http://mempool").unwrap() }),
+            Some(Fallback::Queue { timeout_ms: Fallback::DEFAULT_QUEUE_TIMEOUT_MS }),
+            Some(Fallback::Queue { timeout_ms: 500 }),
+        ]);
+    }
+
+    #[test]
+    fn test_deserialize_deadline() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        [lookahead.deadline]
+        genesis-time = 1606824023
+        cutoff-ms = 1000
+        [[lookahead]]
+        chain-id = 2
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        deadline = { genesis-time = 1606824023, seconds-per-slot = 6, cutoff-ms = 500, action = "reroute" }
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        assert_eq!(
+            config.lookaheads[0].deadline,
+            Some(Deadline {
+                genesis_time: 1606824023,
+                seconds_per_slot: SECONDS_PER_SLOT,
+                cutoff_ms: 1000,
+                action: DeadlineAction::Reject,
+            })
+        );
+        assert_eq!(
+            config.lookaheads[1].deadline,
+            Some(Deadline {
+                genesis_time: 1606824023,
+                seconds_per_slot: 6,
+                cutoff_ms: 500,
+                action: DeadlineAction::Reroute,
+            })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_validation() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        validation = { execution-rpc = "http://execution-node:8545" }
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        assert_eq!(
+            config.lookaheads[0].validation,
+            Some(Validation {
+                execution_rpc: Url::from_str("http://execution-node:8545").unwrap(),
+                timeout_ms: Validation::DEFAULT_TIMEOUT_MS,
+            })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_forward_client() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        [lookahead.client]
+        timeout-ms = 2000
+        pool-max-idle-per-host = 8
+        tcp-nodelay = true
+        prewarm-slots = 4
+        [lookahead.client.preconfers."http://preconfer:8000"]
+        http2-prior-knowledge = true
+        timeout-ms = 500
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        let client = &config.lookaheads[0].client;
+        assert_eq!(client.settings, ClientSettings {
+            timeout_ms: Some(2000),
+            pool_max_idle_per_host: Some(8),
+            tcp_nodelay: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(client.prewarm_slots, 4);
+
+        let preconfer = &client.preconfers[&Url::from_str("http://preconfer:8000").unwrap()];
+        assert_eq!(preconfer.or(&client.settings), ClientSettings {
+            timeout_ms: Some(500),
+            http2_prior_knowledge: Some(true),
+            pool_max_idle_per_host: Some(8),
+            tcp_nodelay: Some(true),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_deserialize_health_probe() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        [[lookahead]]
+        chain-id = 1
+        url-provider = "lookahead"
+        relays = ["relay1"]
+        health-probe = { path = "/status", entries = 2 }
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        assert_eq!(
+            config.lookaheads[0].health_probe,
+            Some(HealthProbe {
+                path: "/status".into(),
+                entries: 2,
+                interval_ms: HealthProbe::DEFAULT_INTERVAL_MS,
+                timeout_ms: HealthProbe::DEFAULT_TIMEOUT_MS,
+            })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_api_keys() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        lookahead = []
+        [[api-keys]]
+        name = "partner"
+        key = "secret"
+        allowed-chain-ids = [1]
+        quota = { requests = 100 }
+        [[api-keys]]
+        name = "internal"
+        key = "other-secret"
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        assert_eq!(config.api_keys, vec![
+            ApiKey {
+                name: "partner".into(),
+                key: "secret".into(),
+                allowed_chain_ids: Some(vec![1]),
+                quota: Some(Quota { requests: 100, period_secs: Quota::DEFAULT_PERIOD_SECS }),
+            },
+            ApiKey {
+                name: "internal".into(),
+                key: "other-secret".into(),
+                allowed_chain_ids: None,
+                quota: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_deserialize_rate_limit() {
+        let data = r#"
+        beacon-nodes = ["node1"]
+        lookahead = []
+        [rate-limit]
+        trusted-proxies = ["10.0.0.1"]
+        per-ip = { capacity = 20, refill-per-sec = 10 }
+        "#;
+        let config: Config = toml::from_str(data).unwrap();
+        assert_eq!(
+            config.rate_limit,
+            Some(RateLimit {
+                trusted_proxies: vec![IpAddr::from([10, 0, 0, 1])],
+                per_ip: Some(TokenBucket { capacity: 20, refill_per_sec: 10 }),
+                per_sender: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_deadline_cutoff() {
+        let deadline = Deadline {
+            genesis_time: 100,
+            seconds_per_slot: 12,
+            cutoff_ms: 1000,
+            action: Default::default(),
+        };
+        assert_eq!(deadline.slot_start_ms(2), 124_000);
+        assert!(!deadline.is_past_cutoff(2, 122_999));
+        assert!(deadline.is_past_cutoff(2, 123_000));
+        assert!(deadline.is_past_cutoff(2, 130_000));
+    }
+
     #[test]
     fn test_fail_if_wrong_registry_combination() {
         let data = r#"
@@ -1,4 +1,6 @@
 pub const EPOCH_SLOTS: u64 = 32;
+pub const SECONDS_PER_SLOT: u64 = 12;
 
 pub const GET_PRECONFER_PATH: &str = "/constraints/v1/preconfer/";
 pub const GET_PRECONFERS_PATH: &str = "/constraints/v1/preconfers";
+pub const SUBSCRIBE_PRECONFERS_PATH: &str = "/constraints/v1/preconfers/stream";
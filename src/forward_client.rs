@@ -0,0 +1,141 @@
+use std::time::{Duration, Instant};
+
+use eyre::{Context, Result};
+use hashbrown::HashMap;
+use http::Extensions;
+use reqwest::Request;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_tracing::{
+    default_on_request_end, reqwest_otel_span, ReqwestOtelSpanBackend, TracingMiddleware,
+};
+use tracing::{debug, Span};
+use url::Url;
+
+use crate::config::{ClientSettings, Config, ForwardClient};
+
+struct TimeTrace;
+
+impl ReqwestOtelSpanBackend for TimeTrace {
+    fn on_request_start(req: &Request, extension: &mut Extensions) -> Span {
+        extension.insert(Instant::now());
+        reqwest_otel_span!(
+            level = tracing::Level::DEBUG,
+            name = "reqwest-http-request",
+            req,
+            time_elapsed = tracing::field::Empty
+        )
+    }
+
+    fn on_request_end(
+        span: &Span,
+        outcome: &reqwest_middleware::Result<reqwest::Response>,
+        extension: &mut Extensions,
+    ) {
+        let time_elapsed = extension.get::<Instant>().unwrap().elapsed().as_millis() as i64;
+        default_on_request_end(span, outcome);
+        span.record("time_elapsed", time_elapsed);
+    }
+}
+
+/// Builds a client forwarding requests with `settings`.
+pub fn build_client(settings: &ClientSettings) -> Result<ClientWithMiddleware> {
+    let timeout_ms = settings.timeout_ms.unwrap_or(ClientSettings::DEFAULT_TIMEOUT_MS);
+    let mut builder = reqwest::ClientBuilder::new().timeout(Duration::from_millis(timeout_ms));
+    if settings.http2_prior_knowledge == Some(true) {
+        builder = builder.http2_prior_knowledge();
+    }
+    if let Some(max_idle) = settings.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(idle_timeout_ms) = settings.pool_idle_timeout_ms {
+        builder = builder.pool_idle_timeout(Duration::from_millis(idle_timeout_ms));
+    }
+    if let Some(keepalive_ms) = settings.tcp_keepalive_ms {
+        builder = builder.tcp_keepalive(Duration::from_millis(keepalive_ms));
+    }
+    if let Some(nodelay) = settings.tcp_nodelay {
+        builder = builder.tcp_nodelay(nodelay);
+    }
+    let client = builder.build().wrap_err("could not build forward client")?;
+    Ok(ClientBuilder::new(client).with(TracingMiddleware::<TimeTrace>::new()).build())
+}
+
+/// `ForwardClients` are the clients the requests of a chain are forwarded with: the client of
+/// the chain, and the clients of the preconfers with their own settings, keyed by origin.
+#[derive(Debug)]
+pub struct ForwardClients {
+    default: ClientWithMiddleware,
+    preconfers: HashMap<String, ClientWithMiddleware>,
+    prewarm_slots: u64,
+}
+
+impl ForwardClients {
+    pub fn new(config: &ForwardClient) -> Result<Self> {
+        let preconfers = config
+            .preconfers
+            .iter()
+            .map(|(url, settings)| {
+                let client = build_client(&settings.or(&config.settings))?;
+                Ok((url.origin().ascii_serialization(), client))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            default: build_client(&config.settings)?,
+            preconfers,
+            prewarm_slots: config.prewarm_slots,
+        })
+    }
+
+    /// Returns the client requests to `url` are forwarded with.
+    pub fn client_for(&self, url: &Url) -> &ClientWithMiddleware {
+        self.preconfers.get(&url.origin().ascii_serialization()).unwrap_or(&self.default)
+    }
+
+    /// Number of slots ahead of the head whose preconfers get a warm connection.
+    pub fn prewarm_slots(&self) -> u64 {
+        self.prewarm_slots
+    }
+
+    /// Opens a connection to the preconfer at `url`, kept in the pool of its client for the
+    /// requests that follow. The response does not matter.
+    pub async fn prewarm(&self, url: Url) {
+        if let Err(err) = self.client_for(&url).head(url.clone()).send().await {
+            debug!(name: "forward_clients.prewarm", %url, ?err, "could not open connection");
+        }
+    }
+}
+
+/// Builds the forward clients of every chain from the configuration, keyed by the chain-id.
+pub fn forward_clients_from_config(config: &Config) -> Result<HashMap<u16, ForwardClients>> {
+    config
+        .lookaheads
+        .iter()
+        .map(|lookahead| Ok((lookahead.chain_id, ForwardClients::new(&lookahead.client)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_client_for_preconfer() {
+        let mut config = ForwardClient::default();
+        config.preconfers.insert(Url::from_str("http://preconfer:8000").unwrap(), ClientSettings {
+            timeout_ms: Some(500),
+            ..Default::default()
+        });
+        let clients = ForwardClients::new(&config).unwrap();
+
+        // preconfers are matched by origin, whatever the path.
+        let url = Url::from_str("http://preconfer:8000/constraints").unwrap();
+        assert!(std::ptr::eq(
+            clients.client_for(&url),
+            &clients.preconfers["http://preconfer:8000"]
+        ));
+        let url = Url::from_str("http://preconfer:9000").unwrap();
+        assert!(std::ptr::eq(clients.client_for(&url), &clients.default));
+    }
+}
@@ -1,35 +1,71 @@
 use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    net::SocketAddr,
     sync::Arc,
     time::{Duration, Instant},
 };
 
+use alloy::rpc::types::beacon::events::HeadEvent;
 use axum::{
-    extract::{Path, State},
-    http::HeaderMap,
-    response::IntoResponse,
-    routing::post,
-    Router,
+    extract::{ConnectInfo, Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderValue},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
 };
 use bytes::Bytes;
 use eyre::{Context, Result};
+use futures::Stream;
 use hashbrown::HashMap;
-use http::Extensions;
-use reqwest::{Request, Response, StatusCode};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_tracing::{
-    default_on_request_end, reqwest_otel_span, ReqwestOtelSpanBackend, TracingMiddleware,
+use reqwest::StatusCode;
+use reqwest_middleware::ClientWithMiddleware;
+use reth_primitives::TransactionSigned;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
 };
-use tokio::task::JoinHandle;
 use tower_http::trace::TraceLayer;
-use tracing::{error, Span};
+use tracing::{error, warn};
 use url::Url;
 
-use crate::lookahead::LookaheadManager;
+use crate::{
+    auth::{ApiKeys, API_KEY_HEADER},
+    config::{ClientSettings, Fallback, HealthProbe},
+    forward_client::{build_client, ForwardClients},
+    lookahead::{error::LookaheadError, unix_time_ms, GasReservation, LookaheadManager},
+    metrics::{self, PRECONFER_PROBES},
+    preconf::{bundle::BundleInclusionRequest, commitments::InclusionRequest},
+    queue::{QueuedRequest, RequestQueue, TicketId, TicketStatus},
+    rate_limit::RateLimits,
+    replay::{CachedResponse, Replay, ReplayCache, ReplayKey},
+    ws::ws_handler,
+};
+
+/// Interval at which expired queued requests, tickets, replayed requests and rate limits are
+/// pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(12);
 
 #[derive(Debug)]
 pub(crate) struct SharedState {
     managers: HashMap<u16, LookaheadManager>,
+    /// Client of the chains without their own `forward_clients`.
     client: ClientWithMiddleware,
+    /// Clients requests are forwarded to preconfers with, by chain.
+    forward_clients: HashMap<u16, ForwardClients>,
+    /// Requests for slots whose preconfer is not known yet.
+    queue: RequestQueue,
+    /// Requests recently handled, to deduplicate replayed requests.
+    replays: ReplayCache,
+    /// API keys allowed to forward requests.
+    api_keys: ApiKeys,
+    rate_limits: RateLimits,
+    /// New heads of the beacon chain, notified to WebSocket subscribers.
+    head_tx: broadcast::Sender<HeadEvent>,
 }
 
 pub(crate) struct RpcForward {
@@ -37,30 +73,6 @@ pub(crate) struct RpcForward {
     shared_state: SharedState,
 }
 
-struct TimeTrace;
-
-impl ReqwestOtelSpanBackend for TimeTrace {
-    fn on_request_start(req: &Request, extension: &mut Extensions) -> Span {
-        extension.insert(Instant::now());
-        reqwest_otel_span!(
-            level = tracing::Level::DEBUG,
-            name = "reqwest-http-request",
-            req,
-            time_elapsed = tracing::field::Empty
-        )
-    }
-
-    fn on_request_end(
-        span: &Span,
-        outcome: &reqwest_middleware::Result<Response>,
-        extension: &mut Extensions,
-    ) {
-        let time_elapsed = extension.get::<Instant>().unwrap().elapsed().as_millis() as i64;
-        default_on_request_end(span, outcome);
-        span.record("time_elapsed", time_elapsed);
-    }
-}
-
 impl SharedState {
     pub fn new(mut managers: HashMap<u16, LookaheadManager>) -> Result<Self> {
         // start lookahead provider for each manager
@@ -69,13 +81,63 @@ impl SharedState {
         }
         Ok(Self {
             managers,
-            client: ClientBuilder::new(
-                reqwest::ClientBuilder::new().timeout(Duration::from_secs(10)).build()?,
-            )
-            .with(TracingMiddleware::<TimeTrace>::new())
-            .build(),
+            client: build_client(&ClientSettings::default())?,
+            forward_clients: HashMap::new(),
+            queue: RequestQueue::default(),
+            replays: ReplayCache::default(),
+            api_keys: ApiKeys::default(),
+            rate_limits: RateLimits::default(),
+            head_tx: broadcast::channel(16).0,
         })
     }
+
+    /// Requires requests to be authenticated with one of `api_keys`, if any.
+    pub fn with_api_keys(mut self, api_keys: ApiKeys) -> Self {
+        self.api_keys = api_keys;
+        self
+    }
+
+    /// Rate limits the forwarded requests by client IP and by transaction sender.
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Notifies the head events of `head_tx` to the WebSocket subscribers of new heads.
+    pub fn with_head_events(mut self, head_tx: broadcast::Sender<HeadEvent>) -> Self {
+        self.head_tx = head_tx;
+        self
+    }
+
+    /// Forwards the requests of each chain with its own clients.
+    pub fn with_forward_clients(mut self, forward_clients: HashMap<u16, ForwardClients>) -> Self {
+        self.forward_clients = forward_clients;
+        self
+    }
+
+    /// Returns the client the requests of `chain_id` to `url` are forwarded with.
+    fn client(&self, chain_id: u16, url: &Url) -> &ClientWithMiddleware {
+        match self.forward_clients.get(&chain_id) {
+            Some(clients) => clients.client_for(url),
+            None => &self.client,
+        }
+    }
+
+    pub fn manager(&self, chain_id: u16) -> Option<&LookaheadManager> {
+        self.managers.get(&chain_id)
+    }
+
+    pub fn subscribe_head_events(&self) -> broadcast::Receiver<HeadEvent> {
+        self.head_tx.subscribe()
+    }
+
+    pub fn api_keys(&self) -> &ApiKeys {
+        &self.api_keys
+    }
+
+    pub fn rate_limits(&self) -> &RateLimits {
+        &self.rate_limits
+    }
 }
 
 impl RpcForward {
@@ -88,18 +150,56 @@ impl RpcForward {
         let listener =
             tokio::net::TcpListener::bind(self.addr).await.wrap_err("failed to bind listener")?;
         Ok(tokio::spawn(async move {
-            axum::serve(listener, app).await?;
+            // The address of the client is used to rate limit its requests.
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
             Ok(())
         }))
     }
 }
 
 fn router(shared_state: SharedState) -> Router {
-    Router::new()
+    let state = Arc::new(shared_state);
+    tokio::spawn(prune_expired(state.clone()));
+    for chain_id in state.managers.keys() {
+        tokio::spawn(release_queued_requests(state.clone(), *chain_id));
+        tokio::spawn(prewarm_connections(state.clone(), *chain_id));
+        tokio::spawn(probe_preconfers(state.clone(), *chain_id));
+    }
+
+    let forward_routes = Router::new()
         .route("/:chain_id", post(scan_id_forward_request))
+        .route("/:chain_id/:api_key", post(scan_id_forward_request_with_key))
+        .route("/ws/:chain_id", get(ws_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit_client_ip));
+
+    Router::new()
+        .merge(forward_routes)
+        .route("/:chain_id/gas", get(gas_budget))
+        .route("/:chain_id/lookahead/stream", get(lookahead_stream))
+        .route("/:chain_id/tickets/:ticket_id", get(ticket_status))
+        .route("/health", get(health))
+        .route("/metrics", get(export_metrics))
         .route("/", post(forward_request))
         .layer(TraceLayer::new_for_http())
-        .with_state(Arc::new(shared_state))
+        .with_state(state)
+}
+
+/// Rejects the requests of clients that exceeded their rate limit with `429 Too Many Requests`.
+/// Requests are not rate limited by IP if the address of the client is unknown.
+async fn rate_limit_client_ip(
+    State(state): State<Arc<SharedState>>,
+    request: axum::extract::Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        let ip = state.rate_limits.client_ip(*peer, request.headers());
+        if !state.rate_limits.check_ip(ip, Instant::now()) {
+            warn!(name: "rate_limits.check_ip", %ip, "rate limit exceeded");
+            return (StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded".to_string())
+                .into_response();
+        }
+    }
+    next.run(request).await
 }
 
 async fn scan_id_forward_request(
@@ -107,21 +207,481 @@ async fn scan_id_forward_request(
     Path(chain_id): Path<u16>,
     headers: HeaderMap,
     body: Bytes,
-) -> Result<impl IntoResponse, impl IntoResponse> {
+) -> Result<Response, Response> {
+    let api_key =
+        headers.get(API_KEY_HEADER).and_then(|key| key.to_str().ok()).map(ToOwned::to_owned);
+    forward_chain_request(state, chain_id, api_key, headers, body).await
+}
+
+/// Same as `scan_id_forward_request`, with the API key given in the path for clients that cannot
+/// set headers.
+async fn scan_id_forward_request_with_key(
+    State(state): State<Arc<SharedState>>,
+    Path((chain_id, api_key)): Path<(u16, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, Response> {
+    forward_chain_request(state, chain_id, Some(api_key), headers, body).await
+}
+
+/// Authorizes, validates and forwards a request to the preconfer of `chain_id`. Requests
+/// received over WebSocket take the same path.
+pub(crate) async fn forward_chain_request(
+    state: Arc<SharedState>,
+    chain_id: u16,
+    api_key: Option<String>,
+    mut headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, Response> {
+    if let Err(err) = state.api_keys.authorize(api_key.as_deref(), chain_id, Instant::now()) {
+        warn!(name: "api_keys.authorize", chain_id, "{}", err);
+        return Err((err.status(), err.to_string()).into_response());
+    }
+    // The API key of the client is not forwarded to preconfers.
+    headers.remove(API_KEY_HEADER);
+
     if let Some(manager) = state.managers.get(&chain_id) {
-        match manager.get_url() {
-            Ok(url) => match inner_forward_request(&state.client, url, body, headers).await {
-                Ok(res) => Ok(res),
+        let request = CommitmentRequest::parse(&headers, &body);
+
+        let replay_key = match request.as_ref().map(|request| request.validate(chain_id)) {
+            Some(Err(err)) => {
+                error!(name: "request.validate", "{}", err);
+                return Err((StatusCode::BAD_REQUEST, err).into_response());
+            }
+            Some(Ok(key)) => Some(key),
+            None => None,
+        };
+
+        // The same signed request is forwarded once, its duplicates get the same response
+        // before they count against the rate limits or their transactions are validated again.
+        if let Some(key) = &replay_key {
+            match state.replays.check(key.clone(), Instant::now()) {
+                Replay::New => {}
+                Replay::InFlight => {
+                    return Err((
+                        StatusCode::CONFLICT,
+                        "the same request is being processed".to_string(),
+                    )
+                        .into_response());
+                }
+                Replay::Completed(response) => return Ok(response.into_response()),
+            }
+        }
+
+        let checked = match &request {
+            Some(request) => check_request(&state, manager, request).await,
+            None => Ok(()),
+        };
+        let response = match checked {
+            Ok(()) => forward_commitment(&state, chain_id, manager, request, headers, body).await,
+            Err(response) => Err(response),
+        };
+        match replay_key {
+            Some(key) => match response {
+                Ok(response) => Ok(cache_response(&state.replays, key, response).await),
+                Err(response) => {
+                    state.replays.remove(&key);
+                    Err(response)
+                }
+            },
+            None => response,
+        }
+    } else {
+        error!(name: "managers.get", "no lookahead provider found for chain-id {}", chain_id);
+        Err((
+            StatusCode::BAD_REQUEST,
+            format!("no lookahead provider found for chain-id {}", chain_id),
+        )
+            .into_response())
+    }
+}
+
+/// Rejects inclusion requests exceeding the rate limit of their senders, with invalid
+/// transactions, or for slots already in the past.
+async fn check_request(
+    state: &SharedState,
+    manager: &LookaheadManager,
+    request: &CommitmentRequest,
+) -> Result<(), Response> {
+    // Inclusion requests are rate limited by the senders of their transactions.
+    if state.rate_limits.has_sender_limit() {
+        let now = Instant::now();
+        for sender in request.txs().iter().filter_map(TransactionSigned::recover_signer) {
+            if !state.rate_limits.check_sender(sender, now) {
+                warn!(name: "rate_limits.check_sender", %sender, "rate limit exceeded");
+                return Err((
+                    StatusCode::TOO_MANY_REQUESTS,
+                    format!("rate limit exceeded for sender {}", sender),
+                )
+                    .into_response());
+            }
+        }
+    }
+
+    // The transactions of inclusion requests are validated against the execution node.
+    // Requests are forwarded if the transactions could not be validated.
+    if let Some(validator) = manager.validator() {
+        for tx in request.txs() {
+            match validator.validate(tx).await {
+                Ok(()) => {}
+                Err(err) if err.is_invalid_transaction() => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        Json(serde_json::json!({
+                            "code": err.code(),
+                            "message": err.to_string(),
+                            "tx_hash": tx.hash(),
+                        })),
+                    )
+                        .into_response());
+                }
+                Err(err) => {
+                    warn!(name: "validator.validate", "{:?}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Requests for slots already in the past cannot be honored.
+    let head_slot = manager.head_slot();
+    if *request.slots().end() < head_slot {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("slot {} is in the past, the head slot is {}", request.slot(), head_slot),
+        )
+            .into_response());
+    }
+    Ok(())
+}
+
+/// Routes and forwards a request to the elected preconfer, or to the fallback when no
+/// preconfer is elected.
+async fn forward_commitment(
+    state: &Arc<SharedState>,
+    chain_id: u16,
+    manager: &LookaheadManager,
+    request: Option<CommitmentRequest>,
+    mut headers: HeaderMap,
+    mut body: Bytes,
+) -> Result<Response, Response> {
+    let mut target = route_request(manager, request.as_ref());
+
+    // When no preconfer is elected, the request is handled by the fallback of the chain.
+    if is_not_elected(&target) {
+        match manager.fallback() {
+            Some(Fallback::Gateway { url }) => target = Ok((url.clone(), None)),
+            Some(Fallback::Mempool { .. })
+                if matches!(request, Some(CommitmentRequest::Bundle(_))) =>
+            {
+                // Bundles are all-or-nothing, which the public mempool cannot guarantee.
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "bundles cannot be sent to the mempool fallback".to_string(),
+                )
+                    .into_response());
+            }
+            Some(Fallback::Mempool { url }) => {
+                if let Some(CommitmentRequest::Inclusion(request)) = &request {
+                    body = match send_raw_transaction_body(&request.tx) {
+                        Ok(body) => body,
+                        Err(err) => return Err(err.into_response()),
+                    };
+                    headers = HeaderMap::new();
+                    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                }
+                target = Ok((url.clone(), None));
+            }
+            Some(Fallback::Queue { timeout_ms }) => {
+                let timeout = Duration::from_millis(*timeout_ms);
+                return queue_request(state, chain_id, manager, request, headers, body, timeout)
+                    .await;
+            }
+            None => {}
+        }
+    }
+
+    match target {
+        Ok((url, reservation)) => {
+            let client = state.client(chain_id, &url);
+            match inner_forward_request(client, url, body, headers).await {
+                Ok(res) => Ok(res.into_response()),
                 Err(err) => {
+                    if let Some(reservation) = reservation {
+                        manager.release_gas(reservation);
+                    }
                     error!(name: "inner_forward_request", "{:?}", err);
                     Err((
                         StatusCode::INTERNAL_SERVER_ERROR,
                         "error while forwarding request".to_string(),
-                    ))
+                    )
+                        .into_response())
                 }
-            },
+            }
+        }
+        Err(err) => {
+            error!(name: "manager.get_url", "{:?}", err);
+            let status = match err.downcast_ref::<LookaheadError>() {
+                Some(LookaheadError::GasLimitExceeded { .. }) |
+                Some(LookaheadError::DeadlineExceeded { .. }) |
+                Some(LookaheadError::NoElectedPreconferInRange { .. }) => StatusCode::BAD_REQUEST,
+                Some(LookaheadError::NoElectedPreconfer) | None => {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            Err((status, err.to_string()).into_response())
+        }
+    }
+}
+
+/// Queues a request until a preconfer is elected for its slot, and waits for at most `timeout`
+/// for it to be forwarded. Requests other than inclusion requests go to the next elected
+/// preconfer. Requests still queued after `timeout` are answered with a ticket to poll.
+async fn queue_request(
+    state: &Arc<SharedState>,
+    chain_id: u16,
+    manager: &LookaheadManager,
+    request: Option<CommitmentRequest>,
+    headers: HeaderMap,
+    body: Bytes,
+    timeout: Duration,
+) -> Result<Response, Response> {
+    // Requests without a slot are released by the first election.
+    let queued = QueuedRequest {
+        slot: request.as_ref().map_or(0, CommitmentRequest::slot),
+        last_slot: request.as_ref().and_then(CommitmentRequest::last_slot),
+        gas: request.as_ref().map_or(0, CommitmentRequest::gas_limit),
+        body,
+        headers,
+    };
+    let slot = queued.slot;
+    let ticket = match state.queue.push(chain_id, queued) {
+        Ok(ticket) => ticket,
+        Err(err) => {
+            error!(name: "queue.push", "{:?}", err);
+            return Err((StatusCode::SERVICE_UNAVAILABLE, err.to_string()).into_response());
+        }
+    };
+    // A preconfer may have been elected since the request was routed.
+    if let Some(elected_slot) = manager.last_elected_slot() {
+        release_queued(state, chain_id, elected_slot);
+    }
+
+    match state.queue.wait(ticket, timeout).await {
+        Some(TicketStatus::Forwarded { response, .. }) => Ok(response.into_response()),
+        Some(TicketStatus::Failed { error }) => {
+            Err((StatusCode::INTERNAL_SERVER_ERROR, error).into_response())
+        }
+        Some(TicketStatus::Expired) => Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("no preconfer elected for slot {}", slot),
+        )
+            .into_response()),
+        Some(TicketStatus::Queued { .. }) | None => Ok((
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "ticket_id": ticket, "slot": slot })),
+        )
+            .into_response()),
+    }
+}
+
+/// Caches the response of a request for its duplicates.
+async fn cache_response(replays: &ReplayCache, key: ReplayKey, response: Response) -> Response {
+    let (parts, body) = response.into_parts();
+    match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => {
+            let response = CachedResponse { status: parts.status, headers: parts.headers, body };
+            replays.complete(key, response.clone());
+            response.into_response()
+        }
+        Err(err) => {
+            replays.remove(&key);
+            error!(name: "cache_response", "{:?}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "error while forwarding request".to_string())
+                .into_response()
+        }
+    }
+}
+
+/// Inclusion request of a single transaction or of a bundle of transactions.
+#[derive(Debug)]
+enum CommitmentRequest {
+    Inclusion(InclusionRequest),
+    Bundle(BundleInclusionRequest),
+}
+
+impl CommitmentRequest {
+    /// Parses the body of a request. Bundles can be SSZ encoded, with the
+    /// `application/octet-stream` content type. Returns `None` for any other request.
+    fn parse(headers: &HeaderMap, body: &Bytes) -> Option<Self> {
+        let is_ssz = headers
+            .get(CONTENT_TYPE)
+            .is_some_and(|content_type| content_type == "application/octet-stream");
+        if is_ssz {
+            return BundleInclusionRequest::from_ssz_bytes(body).ok().map(Self::Bundle);
+        }
+        serde_json::from_slice::<InclusionRequest>(body)
+            .map(Self::Inclusion)
+            .or_else(|_| serde_json::from_slice::<BundleInclusionRequest>(body).map(Self::Bundle))
+            .ok()
+    }
+
+    /// Validates the request sent to `chain_id`. Returns the key deduplicating the request,
+    /// built from the hash signed by the user once the signature is verified.
+    fn validate(&self, chain_id: u16) -> Result<ReplayKey, String> {
+        match self {
+            Self::Inclusion(request) => request
+                .verify(chain_id.into())
+                .and_then(|_| request.validate_blobs())
+                .and_then(|_| request.validate_slot_range())
+                .and_then(|_| request.validate_deadline(unix_time_ms() / 1000))
+                .map(|_| ReplayKey::new(chain_id, request.signing_hash(chain_id.into())))
+                .map_err(|err| err.to_string()),
+            Self::Bundle(bundle) => bundle
+                .validate(chain_id.into())
+                .map(|_| ReplayKey::new(chain_id, bundle.digest(chain_id.into())))
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    fn slot(&self) -> u64 {
+        match self {
+            Self::Inclusion(request) => request.slot,
+            Self::Bundle(bundle) => bundle.slot,
+        }
+    }
+
+    fn slots(&self) -> std::ops::RangeInclusive<u64> {
+        self.slot()..=self.last_slot().unwrap_or(self.slot())
+    }
+
+    /// Last slot of the slot range of the request, if any. Bundles target a single slot.
+    fn last_slot(&self) -> Option<u64> {
+        match self {
+            Self::Inclusion(request) => request.last_slot,
+            Self::Bundle(_) => None,
+        }
+    }
+
+    /// Gas limit of the request, reserved from the budget of the elected preconfer. The gas
+    /// of a bundle is reserved at once since it is included entirely or not at all.
+    fn gas_limit(&self) -> u64 {
+        match self {
+            Self::Inclusion(request) => request.tx.gas_limit(),
+            Self::Bundle(bundle) => bundle.gas_limit(),
+        }
+    }
+
+    fn txs(&self) -> &[TransactionSigned] {
+        match self {
+            Self::Inclusion(request) => std::slice::from_ref(&request.tx),
+            Self::Bundle(bundle) => &bundle.txs,
+        }
+    }
+}
+
+/// Returns the url of the preconfer responsible for the slot of the request, or of the earliest
+/// preconfer elected in its slot range. Inclusion requests consume gas from the budget of the
+/// elected preconfer, any other request is forwarded as is to the next elected preconfer.
+fn route_request(
+    manager: &LookaheadManager,
+    request: Option<&CommitmentRequest>,
+) -> Result<(Url, Option<GasReservation>)> {
+    match request {
+        Some(request) if request.last_slot().is_some() => manager
+            .reserve_gas_in_range(request.slots(), request.gas_limit())
+            .map(|(url, reservation)| (url, Some(reservation))),
+        Some(request) => manager
+            .reserve_gas_for_slot(request.slot(), request.gas_limit())
+            .map(|(url, reservation)| (url, Some(reservation))),
+        None => manager.get_url().map(|url| (url, None)),
+    }
+}
+
+fn is_not_elected<T>(target: &Result<T>) -> bool {
+    target.as_ref().is_err_and(|err| {
+        matches!(
+            err.downcast_ref::<LookaheadError>(),
+            Some(
+                LookaheadError::NoElectedPreconfer |
+                    LookaheadError::NoElectedPreconferInRange { .. }
+            )
+        )
+    })
+}
+
+/// Builds the `eth_sendRawTransaction` JSON-RPC request of a transaction.
+fn send_raw_transaction_body(tx: &TransactionSigned) -> Result<Bytes, (StatusCode, String)> {
+    let mut data = Vec::new();
+    tx.encode_enveloped(&mut data);
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_sendRawTransaction",
+        "params": [format!("0x{}", alloy::hex::encode(&data))],
+    });
+    serde_json::to_vec(&request).map(Bytes::from).map_err(|err| {
+        error!(name: "send_raw_transaction_body", "{:?}", err);
+        (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    })
+}
+
+/// Streams the changes of the lookahead of `chain_id` and the new head slots as server-sent
+/// events, in the format of the beacon node events: the topic as the event name, the kind of
+/// change or `head`, and JSON data.
+async fn lookahead_stream(
+    State(state): State<Arc<SharedState>>,
+    Path(chain_id): Path<u16>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, Response> {
+    let Some(manager) = state.managers.get(&chain_id) else {
+        error!(name: "managers.get", "no lookahead provider found for chain-id {}", chain_id);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("no lookahead provider found for chain-id {}", chain_id),
+        )
+            .into_response());
+    };
+    // Head events are received from every beacon node, each head slot is only sent once.
+    let receivers = (manager.subscribe_changes(), state.subscribe_head_events(), 0);
+    let events = futures::stream::unfold(receivers, move |mut receivers| async move {
+        loop {
+            let event = tokio::select! {
+                change = receivers.0.recv() => change.map(|change| {
+                    let data = serde_json::to_string(&change).unwrap_or_default();
+                    Some(Event::default().event(change.kind()).data(data))
+                }),
+                head = receivers.1.recv() => head.map(|head| {
+                    if head.slot <= receivers.2 {
+                        return None;
+                    }
+                    receivers.2 = head.slot;
+                    let data = serde_json::json!({ "slot": head.slot }).to_string();
+                    Some(Event::default().event("head").data(data))
+                }),
+            };
+            match event {
+                Ok(Some(event)) => return Some((Ok(event), receivers)),
+                Ok(None) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(name: "lookahead_stream", chain_id, skipped, "stream lagging behind");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Returns the remaining gas budget of the next elected preconfer for `chain_id`.
+async fn gas_budget(
+    State(state): State<Arc<SharedState>>,
+    Path(chain_id): Path<u16>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    if let Some(manager) = state.managers.get(&chain_id) {
+        match manager.gas_budget() {
+            Ok(budget) => Ok(Json(budget)),
             Err(err) => {
-                error!(name: "manager.get_url", "{:?}", err);
+                error!(name: "manager.gas_budget", "{:?}", err);
                 Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
             }
         }
@@ -134,6 +694,189 @@ async fn scan_id_forward_request(
     }
 }
 
+/// Returns the status of a queued request.
+async fn ticket_status(
+    State(state): State<Arc<SharedState>>,
+    Path((chain_id, ticket)): Path<(u16, TicketId)>,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    match state.queue.status(chain_id, ticket) {
+        Some(status) => Ok(Json(status)),
+        None => Err((StatusCode::NOT_FOUND, format!("ticket {} not found", ticket))),
+    }
+}
+
+/// Prunes the expired queued requests, tickets, replayed requests and rate limits of all chains.
+async fn prune_expired(state: Arc<SharedState>) {
+    let mut prune_interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        prune_interval.tick().await;
+        state.queue.prune(Instant::now());
+        state.replays.prune(Instant::now());
+        state.rate_limits.prune(Instant::now());
+    }
+}
+
+/// Forwards the queued requests of `chain_id` once preconfers are elected for their slot.
+async fn release_queued_requests(state: Arc<SharedState>, chain_id: u16) {
+    let Some(manager) = state.managers.get(&chain_id) else { return };
+    let mut changes_rx = manager.subscribe_changes();
+    loop {
+        let slot = match changes_rx.recv().await {
+            Ok(change) => match change.elected_slot() {
+                Some(slot) => slot,
+                None => continue,
+            },
+            // Missed insertions are covered by the last slot of the lookahead.
+            Err(RecvError::Lagged(_)) => match manager.last_elected_slot() {
+                Some(slot) => slot,
+                None => continue,
+            },
+            Err(RecvError::Closed) => break,
+        };
+        release_queued(&state, chain_id, slot);
+    }
+}
+
+/// Forwards the queued requests of `chain_id` for the slots up to `slot`.
+fn release_queued(state: &Arc<SharedState>, chain_id: u16, slot: u64) {
+    for (ticket, request) in state.queue.release(chain_id, slot) {
+        let state = state.clone();
+        tokio::spawn(
+            async move { forward_queued_request(&state, chain_id, ticket, request).await },
+        );
+    }
+}
+
+/// Opens connections to the preconfers elected in the next slots on every new head, so that
+/// requests forwarded to them do not wait for a connection to be established.
+async fn prewarm_connections(state: Arc<SharedState>, chain_id: u16) {
+    let (Some(manager), Some(clients)) =
+        (state.managers.get(&chain_id), state.forward_clients.get(&chain_id))
+    else {
+        return;
+    };
+    if clients.prewarm_slots() == 0 {
+        return;
+    }
+    let mut head_rx = state.subscribe_head_events();
+    // Head events are received from every beacon node.
+    let mut last_head_slot = 0;
+    loop {
+        let head_slot = match head_rx.recv().await {
+            Ok(head) if head.slot > last_head_slot => head.slot,
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        last_head_slot = head_slot;
+        let slots = head_slot + 1..=head_slot + clients.prewarm_slots();
+        let urls = manager.preconfer_urls_in_range(slots);
+        futures::future::join_all(urls.into_iter().map(|url| clients.prewarm(url))).await;
+    }
+}
+
+/// Probes the health endpoint of the next elected preconfers of `chain_id` at every interval,
+/// so that requests are routed away from unreachable preconfers before they time out.
+async fn probe_preconfers(state: Arc<SharedState>, chain_id: u16) {
+    let Some(manager) = state.managers.get(&chain_id) else { return };
+    let Some(probe) = manager.health_probe() else { return };
+    let mut interval = tokio::time::interval(Duration::from_millis(probe.interval_ms));
+    let state = &state;
+    loop {
+        interval.tick().await;
+        let urls = manager.next_preconfer_urls(probe.entries);
+        let probes = futures::future::join_all(urls.into_iter().map(|url| async move {
+            let reachable = probe_preconfer(state.client(chain_id, &url), &url, probe).await;
+            (url, reachable)
+        }))
+        .await;
+        manager.preconfer_health().update(probes);
+    }
+}
+
+/// Returns true if the health endpoint of the preconfer at `url` answered without a server error
+/// within the timeout of the probe.
+async fn probe_preconfer(client: &ClientWithMiddleware, url: &Url, probe: &HealthProbe) -> bool {
+    let Ok(health_url) = url.join(&probe.path) else {
+        warn!(name: "probe_preconfer", %url, path = %probe.path, "invalid health endpoint");
+        return true;
+    };
+    let timeout = Duration::from_millis(probe.timeout_ms);
+    let reachable = match client.get(health_url).timeout(timeout).send().await {
+        Ok(res) => !res.status().is_server_error(),
+        Err(err) => {
+            warn!(name: "probe_preconfer", %url, ?err, "preconfer unreachable");
+            false
+        }
+    };
+    let status = if reachable { "reachable" } else { "unreachable" };
+    PRECONFER_PROBES.with_label_values(&[status]).inc();
+    reachable
+}
+
+async fn forward_queued_request(
+    state: &SharedState,
+    chain_id: u16,
+    ticket: TicketId,
+    request: QueuedRequest,
+) {
+    let Some(manager) = state.managers.get(&chain_id) else { return };
+    let target = match request.last_slot {
+        Some(last_slot) => manager.reserve_gas_in_range(request.slot..=last_slot, request.gas),
+        None => manager.reserve_gas_for_slot(request.slot, request.gas),
+    };
+    let status = match target {
+        Ok((url, reservation)) => {
+            let client = state.client(chain_id, &url);
+            match inner_forward_request(client, url, request.body, request.headers).await {
+                Ok(response) => TicketStatus::Forwarded {
+                    slot: reservation.slot,
+                    response: String::from_utf8_lossy(&response).into_owned(),
+                },
+                Err(err) => {
+                    manager.release_gas(reservation);
+                    error!(name: "inner_forward_request", "{:?}", err);
+                    TicketStatus::Failed { error: "error while forwarding request".to_string() }
+                }
+            }
+        }
+        Err(err) => {
+            error!(name: "manager.reserve_gas_for_slot", "{:?}", err);
+            TicketStatus::Failed { error: err.to_string() }
+        }
+    };
+    state.queue.complete(ticket, status);
+}
+
+/// Reports the health of the relays of every chain. A chain is unhealthy if all of its relays
+/// have their circuit breaker open, in which case `503 Service Unavailable` is returned.
+async fn health(State(state): State<Arc<SharedState>>) -> impl IntoResponse {
+    let mut chains = BTreeMap::new();
+    let mut healthy = true;
+    for (chain_id, manager) in state.managers.iter() {
+        let relays = manager.relay_health();
+        let chain_healthy = relays.is_empty() || relays.iter().any(|relay| !relay.circuit_open);
+        healthy &= chain_healthy;
+        chains.insert(
+            *chain_id,
+            serde_json::json!({
+                "healthy": chain_healthy,
+                "relays": relays,
+                "unreachable_preconfers": manager.preconfer_health().unreachable(),
+            }),
+        );
+    }
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(chains))
+}
+
+/// Exposes the service metrics in the prometheus text format.
+async fn export_metrics() -> Result<impl IntoResponse, impl IntoResponse> {
+    metrics::encode().map_err(|err| {
+        error!(name: "metrics.encode", "{:?}", err);
+        (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    })
+}
+
 async fn forward_request(State(_state): State<Arc<SharedState>>) -> impl IntoResponse {
     (StatusCode::BAD_REQUEST, "missing chain-id parameter")
 }
@@ -143,7 +886,7 @@ async fn inner_forward_request(
     to_addr: Url,
     bytes: Bytes,
     headers: HeaderMap,
-) -> Result<impl IntoResponse> {
+) -> Result<Bytes> {
     let res = client.post(to_addr).body(bytes).headers(headers).send().await?;
     let body = res.bytes().await?;
     Ok(body)
@@ -153,31 +896,62 @@ async fn inner_forward_request(
 mod test {
     use std::{
         default::Default,
+        net::SocketAddr,
         str::FromStr,
-        sync::{Arc, Mutex},
-        time::Duration,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, SystemTime, UNIX_EPOCH},
     };
 
-    use alloy::rpc::types::beacon::{constants::BLS_PUBLIC_KEY_BYTES_LEN, BlsPublicKey};
+    use alloy::{
+        primitives::{Signature, B256},
+        rpc::types::beacon::{
+            constants::BLS_PUBLIC_KEY_BYTES_LEN, events::HeadEvent, BlsPublicKey,
+        },
+    };
     use axum::{
         extract::State,
         http::HeaderMap,
         response::IntoResponse,
-        routing::{get, post},
+        routing::{get, head, post},
         Router,
     };
     use bytes::Bytes;
-    use dashmap::DashMap;
     use eyre::Result;
+    use futures::{SinkExt, StreamExt};
     use hashbrown::HashMap;
     use http::{HeaderValue, StatusCode};
-    use tokio::task::JoinHandle;
+    use reqwest_eventsource::EventSource;
+    use reth_primitives::{sign_message, Transaction, TransactionSigned, TxLegacy};
+    use serde_json::json;
+    use tokio::{sync::broadcast, task::JoinHandle};
+    use tokio_tungstenite::{
+        connect_async,
+        tungstenite::{client::IntoClientRequest, Message},
+        MaybeTlsStream, WebSocketStream,
+    };
     use url::Url;
 
     use crate::{
+        auth::{ApiKeys, API_KEY_HEADER},
+        config::{
+            ApiKey, Deadline, DeadlineAction, Fallback, ForwardClient, HealthProbe, Quota,
+            RateLimit, TokenBucket, Validation,
+        },
+        forward_client::ForwardClients,
         forward_service::{router, SharedState},
-        lookahead::{Lookahead, LookaheadEntry, LookaheadManager, LookaheadProvider, UrlProvider},
-        preconf::election::{PreconferElection, SignedPreconferElection},
+        lookahead::{
+            Lookahead, LookaheadEntry, LookaheadManager, LookaheadMap, LookaheadProvider,
+            UrlProvider,
+        },
+        preconf::{
+            bundle::BundleInclusionRequest,
+            commitments::InclusionRequest,
+            election::{PreconferElection, SignedPreconferElection},
+        },
+        rate_limit::RateLimits,
     };
 
     struct DummySharedState {
@@ -261,13 +1035,13 @@ mod test {
 
     #[tokio::test]
     async fn test_unavailable_forwarded_service() -> Result<()> {
-        let map = Arc::new(DashMap::new());
+        let map = Arc::new(LookaheadMap::default());
         map.insert(0, LookaheadEntry {
             url: "http://not-a-valid-url".into(),
             ..Default::default()
         });
         let manager = LookaheadManager::new(
-            Lookahead { map },
+            Lookahead { map, ..Default::default() },
             LookaheadProvider::None,
             UrlProvider::LookaheadEntry,
         );
@@ -284,13 +1058,13 @@ mod test {
 
     #[tokio::test]
     async fn test_forward_request() -> Result<()> {
-        let map = Arc::new(DashMap::new());
+        let map = Arc::new(LookaheadMap::default());
         map.insert(0, LookaheadEntry {
             url: "http://localhost:12004".into(),
             ..Default::default()
         });
         let manager = LookaheadManager::new(
-            Lookahead { map },
+            Lookahead { map, ..Default::default() },
             LookaheadProvider::None,
             UrlProvider::LookaheadEntry,
         );
@@ -314,7 +1088,7 @@ mod test {
 
     #[tokio::test]
     async fn test_url_map_request() -> Result<()> {
-        let map = Arc::new(DashMap::new());
+        let map = Arc::new(LookaheadMap::default());
         let signature: BlsPublicKey = BlsPublicKey::from([42u8; BLS_PUBLIC_KEY_BYTES_LEN]);
         let mut url_mapping = HashMap::new();
         url_mapping.insert(signature, Url::from_str("http://localhost:12006").unwrap());
@@ -329,7 +1103,7 @@ mod test {
             },
         });
         let manager = LookaheadManager::new(
-            Lookahead { map },
+            Lookahead { map, ..Default::default() },
             LookaheadProvider::None,
             UrlProvider::UrlMap(url_mapping),
         );
@@ -354,12 +1128,12 @@ mod test {
     #[tokio::test]
     async fn test_no_pubkey() -> Result<()> {
         let signature: BlsPublicKey = BlsPublicKey::from([42u8; BLS_PUBLIC_KEY_BYTES_LEN]);
-        let map = Arc::new(DashMap::new());
+        let map = Arc::new(LookaheadMap::default());
         let mut provider = HashMap::new();
         provider.insert(signature, Url::from_str("http://localhost:12010/1").unwrap());
         map.insert(0, LookaheadEntry { url: "".into(), ..Default::default() });
         let manager = LookaheadManager::new(
-            Lookahead { map },
+            Lookahead { map, ..Default::default() },
             LookaheadProvider::None,
             UrlProvider::UrlMap(provider),
         );
@@ -395,6 +1169,1049 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_gas_limit_exceeded() -> Result<()> {
+        let map = Arc::new(LookaheadMap::default());
+        map.insert(0, LookaheadEntry {
+            url: "http://preconfer".into(),
+            election: SignedPreconferElection {
+                message: PreconferElection { gas_limit: Some(20_000), ..Default::default() },
+                ..Default::default()
+            },
+        });
+        let manager = LookaheadManager::new(
+            Lookahead { map, ..Default::default() },
+            LookaheadProvider::None,
+            UrlProvider::LookaheadEntry,
+        );
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+        let res = reqwest::Client::new()
+            .post(format!("{}/1", forwarder))
+            .json(&inclusion_request(21_000))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.text().await?,
+            "inclusion request gas 21000 exceeds the remaining gas 20000 of the preconfer elected for slot 0"
+        );
+
+        let budget: serde_json::Value =
+            reqwest::get(format!("{}/1/gas", forwarder)).await?.json().await?;
+        assert_eq!(budget["gas_limit"], 20_000);
+        assert_eq!(budget["gas_used"], 0);
+        assert_eq!(budget["gas_remaining"], 20_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_signature() -> Result<()> {
+        let mut managers = HashMap::new();
+        managers.insert(1u16, LookaheadManager::default());
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        // no address can be recovered from a zero signature.
+        let request = InclusionRequest {
+            signature: Signature::from_str(&"00".repeat(65)).unwrap(),
+            ..inclusion_request(21_000)
+        };
+        let res =
+            reqwest::Client::new().post(format!("{}/1", forwarder)).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text().await?, "invalid inclusion request signature");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health() -> Result<()> {
+        let mut managers = HashMap::new();
+        managers.insert(1u16, LookaheadManager::default());
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+        let res = reqwest::get(format!("{}/health", forwarder)).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let health: serde_json::Value = res.json().await?;
+        assert_eq!(health["1"]["healthy"], true);
+        assert_eq!(health["1"]["relays"], serde_json::json!([]));
+
+        let res = reqwest::get(format!("{}/metrics", forwarder)).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_gateway() -> Result<()> {
+        let gateway = spawn_counting_preconfer().await;
+        let fallback = Fallback::Gateway { url: Url::from_str(&gateway)? };
+        let mut managers = HashMap::new();
+        managers.insert(1u16, LookaheadManager::default().with_fallback(Some(fallback)));
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        for _ in 0..2 {
+            assert_eq!(send_plain_request(&forwarder).await?.status(), StatusCode::OK);
+        }
+
+        let cnt_res = reqwest::get(format!("{}/cnt", gateway)).await.unwrap();
+        assert_eq!(cnt_res.text().await.unwrap(), "2");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_mempool() -> Result<()> {
+        // the mempool echoes the JSON-RPC request it received.
+        let mempool = spawn_echo_preconfer().await;
+        let fallback = Fallback::Mempool { url: Url::from_str(&mempool)? };
+        let mut managers = HashMap::new();
+        managers.insert(1u16, LookaheadManager::default().with_fallback(Some(fallback)));
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        let request = inclusion_request(21_000);
+        let res =
+            reqwest::Client::new().post(format!("{}/1", forwarder)).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let rpc_request: serde_json::Value = res.json().await?;
+        assert_eq!(rpc_request["method"], "eth_sendRawTransaction");
+        assert_eq!(
+            rpc_request["params"][0],
+            serde_json::to_value(&request)?["tx"],
+            "the raw transaction is the enveloped transaction of the inclusion request"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fallback_queue_until_elected() -> Result<()> {
+        let preconfer = spawn_counting_preconfer().await;
+        let mut lookahead = Lookahead::default();
+        let manager = LookaheadManager::new(
+            lookahead.clone(),
+            LookaheadProvider::None,
+            UrlProvider::LookaheadEntry,
+        )
+        .with_fallback(Some(Fallback::Queue { timeout_ms: 3_000 }));
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        let url = preconfer.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            lookahead.insert(0, LookaheadEntry { url, ..Default::default() });
+        });
+        assert_eq!(send_plain_request(&forwarder).await?.status(), StatusCode::OK);
+
+        let cnt_res = reqwest::get(format!("{}/cnt", preconfer)).await.unwrap();
+        assert_eq!(cnt_res.text().await.unwrap(), "1");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_queue_until_slot_elected() -> Result<()> {
+        let preconfer = spawn_echo_preconfer().await;
+        let lookahead = Lookahead::default();
+        lookahead.map.insert(0, LookaheadEntry { url: preconfer.clone(), ..Default::default() });
+        let manager = LookaheadManager::new(
+            lookahead.clone(),
+            LookaheadProvider::None,
+            UrlProvider::LookaheadEntry,
+        )
+        .with_fallback(Some(Fallback::Queue { timeout_ms: 100 }));
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        let request = sign_request(InclusionRequest { slot: 5, ..inclusion_request(21_000) });
+        let res =
+            reqwest::Client::new().post(format!("{}/1", forwarder)).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        let ticket: serde_json::Value = res.json().await?;
+        let ticket_url = format!("{}/1/tickets/{}", forwarder, ticket["ticket_id"]);
+        let status: serde_json::Value = reqwest::get(&ticket_url).await?.json().await?;
+        assert_eq!(status, serde_json::json!({ "status": "queued", "slot": 5 }));
+
+        lookahead.clone().insert(6, LookaheadEntry {
+            url: preconfer,
+            election: SignedPreconferElection {
+                message: PreconferElection { slot_number: 6, ..Default::default() },
+                ..Default::default()
+            },
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let status: serde_json::Value = reqwest::get(&ticket_url).await?.json().await?;
+        assert_eq!(status["status"], "forwarded");
+        assert_eq!(status["slot"], 6);
+        assert_eq!(status["response"], serde_json::to_string(&request)?);
+
+        let res = reqwest::get(format!("{}/1/tickets/1000", forwarder)).await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_route_to_preconfer_of_slot() -> Result<()> {
+        let mut lookahead = Lookahead::default();
+        for (slot, response) in [(10, "slot 10"), (12, "slot 12")] {
+            let url = serve(Router::new().route("/", post(move || async move { response }))).await;
+            lookahead.insert(slot, LookaheadEntry {
+                url,
+                election: SignedPreconferElection {
+                    message: PreconferElection {
+                        slot_number: slot,
+                        gas_limit: Some(30_000),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            });
+        }
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(
+                lookahead.clone(),
+                LookaheadProvider::None,
+                UrlProvider::LookaheadEntry,
+            ),
+        );
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        // a request for slot N goes to the first preconfer elected at or after N.
+        let request = sign_request(InclusionRequest { slot: 11, ..inclusion_request(21_000) });
+        let res =
+            reqwest::Client::new().post(format!("{}/1", forwarder)).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await?, "slot 12");
+
+        assert_eq!(lookahead.gas_tracker.budget(12, Some(30_000)).gas_used, 21_000);
+        assert_eq!(lookahead.gas_tracker.budget(10, Some(30_000)).gas_used, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deadline_cutoff() -> Result<()> {
+        let preconfer = spawn_counting_preconfer().await;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        // the block of slot 100 is proposed now.
+        let deadline = |action| Deadline {
+            genesis_time: now - 100 * 12,
+            seconds_per_slot: 12,
+            cutoff_ms: 1000,
+            action,
+        };
+        let lookahead = |entries: Vec<(u64, &str)>| {
+            let map = Arc::new(LookaheadMap::default());
+            for (slot, url) in entries {
+                map.insert(slot, LookaheadEntry {
+                    url: url.into(),
+                    election: SignedPreconferElection {
+                        message: PreconferElection { slot_number: slot, ..Default::default() },
+                        ..Default::default()
+                    },
+                });
+            }
+            Lookahead { map, ..Default::default() }
+        };
+
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(
+                lookahead(vec![(100, "http://not-a-valid-url"), (105, preconfer.as_str())]),
+                LookaheadProvider::None,
+                UrlProvider::LookaheadEntry,
+            )
+            .with_deadline(Some(deadline(DeadlineAction::Reroute))),
+        );
+        managers.insert(
+            2u16,
+            LookaheadManager::new(
+                lookahead(vec![(100, preconfer.as_str())]),
+                LookaheadProvider::None,
+                UrlProvider::LookaheadEntry,
+            )
+            .with_deadline(Some(deadline(DeadlineAction::Reject))),
+        );
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        // rerouted to the preconfer of slot 105.
+        assert_eq!(send_plain_request(&forwarder).await?.status(), StatusCode::OK);
+        let cnt_res = reqwest::get(format!("{}/cnt", preconfer)).await?;
+        assert_eq!(cnt_res.text().await?, "1");
+
+        let res = reqwest::Client::new().post(format!("{}/2", forwarder)).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.text().await?,
+            "request arrived less than 1000 ms before the block proposal of slot 100"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_invalid_transaction() -> Result<()> {
+        let map = Arc::new(LookaheadMap::default());
+        map.insert(0, LookaheadEntry { url: "http://preconfer".into(), ..Default::default() });
+        let validation = Validation {
+            execution_rpc: Url::from_str("http://execution-node:8545")?,
+            timeout_ms: Validation::DEFAULT_TIMEOUT_MS,
+        };
+        let manager = LookaheadManager::new(
+            Lookahead { map, ..Default::default() },
+            LookaheadProvider::None,
+            UrlProvider::LookaheadEntry,
+        )
+        .with_validator(Some(validation.into()));
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        // the transaction is not signed.
+        let res = reqwest::Client::new()
+            .post(format!("{}/1", forwarder))
+            .json(&inclusion_request(21_000))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let error: serde_json::Value = res.json().await?;
+        assert_eq!(error["code"], "invalid_signature");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forward_bundle() -> Result<()> {
+        let preconfer = spawn_echo_preconfer().await;
+        let map = Arc::new(LookaheadMap::default());
+        map.insert(0, LookaheadEntry {
+            url: preconfer,
+            election: SignedPreconferElection {
+                message: PreconferElection { gas_limit: Some(50_000), ..Default::default() },
+                ..Default::default()
+            },
+        });
+        let mut managers = HashMap::new();
+        for chain_id in [1u16, 2] {
+            let manager = LookaheadManager::new(
+                Lookahead { map: map.clone(), ..Default::default() },
+                LookaheadProvider::None,
+                UrlProvider::LookaheadEntry,
+            );
+            managers.insert(chain_id, manager);
+        }
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+
+        // the transactions and the signature of the bundle are for chain 1.
+        let bundle = bundle_request(vec![0, 1]);
+        let res =
+            reqwest::Client::new().post(format!("{}/2", forwarder)).json(&bundle).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text().await?, "bundle transactions are not for chain-id 2");
+
+        let res =
+            reqwest::Client::new().post(format!("{}/1", forwarder)).json(&bundle).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await?, serde_json::to_string(&bundle)?);
+
+        // the gas of the whole bundle is reserved at once.
+        let budget: serde_json::Value =
+            reqwest::get(format!("{}/1/gas", forwarder)).await?.json().await?;
+        assert_eq!(budget["gas_used"], 42_000);
+
+        let res = reqwest::Client::new()
+            .post(format!("{}/1", forwarder))
+            .header("Content-Type", "application/octet-stream")
+            .body(bundle_request(vec![2]).as_ssz_bytes())
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = reqwest::Client::new()
+            .post(format!("{}/1", forwarder))
+            .json(&bundle_request(vec![3, 3]))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(res.text().await?.contains("more than once in the bundle"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slot_range_request() -> Result<()> {
+        let preconfer = spawn_echo_preconfer().await;
+        let map = Arc::new(LookaheadMap::default());
+        for (slot, url, gas_limit) in [
+            (3, "http://not-a-valid-url", Some(20_000)),
+            (5, preconfer.as_str(), None),
+            (8, "http://not-a-valid-url", None),
+        ] {
+            map.insert(slot, LookaheadEntry {
+                url: url.into(),
+                election: SignedPreconferElection {
+                    message: PreconferElection {
+                        slot_number: slot,
+                        gas_limit,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            });
+        }
+        let manager = LookaheadManager::new(
+            Lookahead { map, ..Default::default() },
+            LookaheadProvider::None,
+            UrlProvider::LookaheadEntry,
+        );
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+        let url = format!("{}/1", forwarder);
+
+        // the preconfer of slot 3 has not enough gas left, the request goes to slot 5.
+        let request = sign_request(InclusionRequest {
+            slot: 2,
+            last_slot: Some(6),
+            ..inclusion_request(21_000)
+        });
+        let res = reqwest::Client::new().post(&url).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await?, serde_json::to_string(&request)?);
+
+        let request = sign_request(InclusionRequest {
+            slot: 6,
+            last_slot: Some(7),
+            ..inclusion_request(21_000)
+        });
+        let res = reqwest::Client::new().post(&url).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text().await?, "no preconfer elected between slots 6 and 7");
+
+        let request = sign_request(InclusionRequest {
+            slot: 6,
+            last_slot: Some(5),
+            ..inclusion_request(21_000)
+        });
+        let res = reqwest::Client::new().post(&url).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deduplicate_requests() -> Result<()> {
+        // the preconfer counts the requests it received.
+        let forwarded = Arc::new(AtomicUsize::new(0));
+        let counter = forwarded.clone();
+        let preconfer = serve(Router::new().route(
+            "/",
+            post(move || {
+                let counter = counter.clone();
+                async move { counter.fetch_add(1, Ordering::Relaxed).to_string() }
+            }),
+        ))
+        .await;
+        let mut lookahead = Lookahead::default();
+        lookahead.insert(20, LookaheadEntry {
+            url: preconfer,
+            election: SignedPreconferElection {
+                message: PreconferElection { slot_number: 20, ..Default::default() },
+                ..Default::default()
+            },
+        });
+        lookahead.clear_slots(10);
+        let manager =
+            LookaheadManager::new(lookahead, LookaheadProvider::None, UrlProvider::LookaheadEntry);
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+        let url = format!("{}/1", forwarder);
+
+        let request = sign_request(InclusionRequest { slot: 15, ..inclusion_request(21_000) });
+        for _ in 0..3 {
+            let res = reqwest::Client::new().post(&url).json(&request).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.text().await?, "0", "duplicates get the response to the first request");
+        }
+        assert_eq!(forwarded.load(Ordering::Relaxed), 1);
+
+        // another signature of the same request is a replay too.
+        let signature = sign_message(B256::repeat_byte(2), request.signing_hash(1)).unwrap();
+        let signature =
+            Signature::from_rs_and_parity(signature.r, signature.s, signature.odd_y_parity)
+                .unwrap();
+        let res = reqwest::Client::new()
+            .post(&url)
+            .json(&InclusionRequest { signature, ..request })
+            .send()
+            .await?;
+        assert_eq!(res.text().await?, "0");
+        assert_eq!(forwarded.load(Ordering::Relaxed), 1);
+
+        let request = sign_request(InclusionRequest { slot: 5, ..inclusion_request(21_000) });
+        let res = reqwest::Client::new().post(&url).json(&request).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text().await?, "slot 5 is in the past, the head slot is 10");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_key_auth() -> Result<()> {
+        let preconfer = spawn_counting_preconfer().await;
+        let map = Arc::new(LookaheadMap::default());
+        map.insert(0, LookaheadEntry { url: preconfer.clone(), ..Default::default() });
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(
+                Lookahead { map, ..Default::default() },
+                LookaheadProvider::None,
+                UrlProvider::LookaheadEntry,
+            ),
+        );
+        let api_keys = ApiKeys::new(vec![ApiKey {
+            name: "auth-test".into(),
+            key: "secret".into(),
+            allowed_chain_ids: Some(vec![1]),
+            quota: Some(Quota { requests: 2, period_secs: 60 }),
+        }]);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?.with_api_keys(api_keys)).await;
+
+        let send = |path: &str, api_key: Option<&str>| {
+            let mut request = reqwest::Client::new()
+                .post(format!("{}{}", forwarder, path))
+                .header("Content-Type", "application/json")
+                .body("dummy plain body");
+            if let Some(api_key) = api_key {
+                request = request.header(API_KEY_HEADER, api_key);
+            }
+            request.send()
+        };
+
+        let res = send("/1", None).await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = send("/1", Some("wrong")).await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = send("/2", Some("secret")).await?;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = send("/1", Some("secret")).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = send("/1/secret", None).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = send("/1/secret", None).await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let cnt_res = reqwest::get(format!("{}/cnt", preconfer)).await?;
+        assert_eq!(cnt_res.text().await?, "2");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits() -> Result<()> {
+        let preconfer = spawn_echo_preconfer().await;
+        let map = Arc::new(LookaheadMap::default());
+        map.insert(0, LookaheadEntry { url: preconfer, ..Default::default() });
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(
+                Lookahead { map, ..Default::default() },
+                LookaheadProvider::None,
+                UrlProvider::LookaheadEntry,
+            ),
+        );
+        let rate_limits = RateLimits::from(RateLimit {
+            trusted_proxies: vec![],
+            per_ip: Some(TokenBucket { capacity: 3, refill_per_sec: 0 }),
+            per_sender: Some(TokenBucket { capacity: 1, refill_per_sec: 0 }),
+        });
+        let forwarder =
+            spawn_forwarder(SharedState::new(managers)?.with_rate_limits(rate_limits)).await;
+        let url = format!("{}/1", forwarder);
+
+        let res = reqwest::Client::new().post(&url).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = reqwest::Client::new().post(&url).json(&signed_request(0)).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // same sender.
+        let res = reqwest::Client::new().post(&url).json(&signed_request(1)).send().await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.text().await?.starts_with("rate limit exceeded for sender"));
+
+        // same client IP.
+        let res = reqwest::Client::new().post(&url).send().await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.text().await?, "rate limit exceeded");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_skips_rate_limits() -> Result<()> {
+        let preconfer = spawn_echo_preconfer().await;
+        let mut lookahead = Lookahead::default();
+        lookahead.insert(0, LookaheadEntry { url: preconfer, ..Default::default() });
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(lookahead, LookaheadProvider::None, UrlProvider::LookaheadEntry),
+        );
+        let rate_limits = RateLimits::from(RateLimit {
+            trusted_proxies: vec![],
+            per_ip: None,
+            per_sender: Some(TokenBucket { capacity: 1, refill_per_sec: 0 }),
+        });
+        let forwarder =
+            spawn_forwarder(SharedState::new(managers)?.with_rate_limits(rate_limits)).await;
+
+        let request = signed_request(0);
+        for _ in 0..2 {
+            let res = reqwest::Client::new()
+                .post(format!("{}/1", forwarder))
+                .json(&request)
+                .send()
+                .await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.text().await?, serde_json::to_string(&request)?);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_websocket() -> Result<()> {
+        let preconfer = spawn_echo_preconfer().await;
+        let mut lookahead = Lookahead::default();
+        lookahead.insert(0, LookaheadEntry { url: preconfer, ..Default::default() });
+        let manager = LookaheadManager::new(
+            lookahead.clone(),
+            LookaheadProvider::None,
+            UrlProvider::LookaheadEntry,
+        );
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let (head_tx, _head_rx) = broadcast::channel(16);
+        let forwarder =
+            spawn_forwarder(SharedState::new(managers)?.with_head_events(head_tx.clone())).await;
+        let ws_url = forwarder.replace("http://", "ws://");
+
+        assert!(connect_async(format!("{}/ws/2", ws_url)).await.is_err(), "unknown chain-id");
+        let (mut ws, _) = connect_async(format!("{}/ws/1", ws_url)).await?;
+        let res =
+            call(&mut ws, json!({ "id": 1, "method": "subscribe", "params": ["heads"] })).await?;
+        assert_eq!(res["result"], 1);
+        let res = call(&mut ws, json!({ "id": 2, "method": "subscribe", "params": ["lookahead"] }))
+            .await?;
+        assert_eq!(res["result"], 2);
+        let res = call(&mut ws, json!({ "id": 3, "method": "unknown" })).await?;
+        assert_eq!(res["error"]["code"], -32601);
+
+        let request = inclusion_request(21_000);
+        let res = call(
+            &mut ws,
+            json!({
+                "id": 4,
+                "method": "preconf_sendRequest",
+                "params": [request],
+            }),
+        )
+        .await?;
+        assert_eq!(res["id"], 4);
+        assert_eq!(res["result"], serde_json::to_value(&request)?);
+
+        head_tx.send(head_event(32))?;
+        let notification = next_message(&mut ws).await?;
+        assert_eq!(notification["method"], "subscription");
+        assert_eq!(notification["params"], json!({ "subscription": 1, "result": { "slot": 32 } }));
+        // the same head from another beacon node.
+        head_tx.send(head_event(32))?;
+        head_tx.send(head_event(33))?;
+        let notification = next_message(&mut ws).await?;
+        assert_eq!(notification["params"], json!({ "subscription": 1, "result": { "slot": 33 } }));
+
+        lookahead.insert(40, LookaheadEntry::default());
+        let notification = next_message(&mut ws).await?;
+        assert_eq!(
+            notification["params"],
+            json!({ "subscription": 2, "result": { "change": "inserted", "slot": 40 } })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_websocket_auth_and_rate_limits() -> Result<()> {
+        let mut lookahead = Lookahead::default();
+        lookahead
+            .insert(0, LookaheadEntry { url: spawn_echo_preconfer().await, ..Default::default() });
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(lookahead, LookaheadProvider::None, UrlProvider::LookaheadEntry),
+        );
+        let api_keys = ApiKeys::new(vec![ApiKey {
+            name: "ws-test".into(),
+            key: "secret".into(),
+            allowed_chain_ids: Some(vec![1]),
+            quota: None,
+        }]);
+        // two upgrades and two messages.
+        let rate_limits = RateLimits::from(RateLimit {
+            trusted_proxies: vec![],
+            per_ip: Some(TokenBucket { capacity: 4, refill_per_sec: 0 }),
+            per_sender: None,
+        });
+        let state =
+            SharedState::new(managers)?.with_api_keys(api_keys).with_rate_limits(rate_limits);
+        let ws_url = format!("{}/ws/1", spawn_forwarder(state).await.replace("http://", "ws://"));
+
+        // subscriptions require a key too.
+        assert!(connect_async(&ws_url).await.is_err(), "missing API key");
+        let mut request = ws_url.as_str().into_client_request()?;
+        request.headers_mut().insert(API_KEY_HEADER, HeaderValue::from_static("secret"));
+        let (mut ws, _) = connect_async(request).await?;
+
+        for id in 1..=2 {
+            let res =
+                call(&mut ws, json!({ "id": id, "method": "subscribe", "params": ["heads"] }))
+                    .await?;
+            assert_eq!(res["result"], id);
+        }
+        let res =
+            call(&mut ws, json!({ "id": 3, "method": "subscribe", "params": ["heads"] })).await?;
+        assert_eq!(res["error"]["message"], "rate limit exceeded");
+        assert_eq!(res["error"]["data"], json!({ "status": 429 }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lookahead_stream() -> Result<()> {
+        let mut lookahead = Lookahead::default();
+        let manager = LookaheadManager::new(
+            lookahead.clone(),
+            LookaheadProvider::None,
+            UrlProvider::LookaheadEntry,
+        );
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let (head_tx, _head_rx) = broadcast::channel(16);
+        let forwarder =
+            spawn_forwarder(SharedState::new(managers)?.with_head_events(head_tx.clone())).await;
+
+        let res = reqwest::get(format!("{}/2/lookahead/stream", forwarder)).await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let mut events = EventSource::get(format!("{}/1/lookahead/stream", forwarder));
+        assert!(matches!(events.next().await, Some(Ok(reqwest_eventsource::Event::Open))));
+        lookahead.insert(40, LookaheadEntry::default());
+        // nothing is cleared below slot 40, no event is sent.
+        lookahead.clear_slots(40);
+        lookahead.clear_slots(41);
+        head_tx.send(head_event(41))?;
+        // the same head from another beacon node, and a stale one.
+        head_tx.send(head_event(41))?;
+        head_tx.send(head_event(40))?;
+        head_tx.send(head_event(42))?;
+
+        for (topic, data) in [
+            ("inserted", json!({ "change": "inserted", "slot": 40 })),
+            ("expired", json!({ "change": "expired", "head_slot": 41, "slots": [40] })),
+            ("head", json!({ "slot": 41 })),
+            ("head", json!({ "slot": 42 })),
+        ] {
+            match events.next().await {
+                Some(Ok(reqwest_eventsource::Event::Message(message))) => {
+                    assert_eq!(message.event, topic);
+                    assert_eq!(serde_json::from_str::<serde_json::Value>(&message.data)?, data);
+                }
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+        events.close();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prewarm_connections() -> Result<()> {
+        // the preconfer counts the connections opened to it.
+        let prewarmed = Arc::new(AtomicUsize::new(0));
+        let counter = prewarmed.clone();
+        let preconfer = serve(Router::new().route(
+            "/",
+            head(move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+            }),
+        ))
+        .await;
+        let mut lookahead = Lookahead::default();
+        lookahead.insert(12, LookaheadEntry { url: preconfer, ..Default::default() });
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(lookahead, LookaheadProvider::None, UrlProvider::LookaheadEntry),
+        );
+        let mut forward_clients = HashMap::new();
+        forward_clients.insert(1u16, ForwardClients::new(&ForwardClient::default())?);
+        let (head_tx, _head_rx) = broadcast::channel(16);
+        let state = SharedState::new(managers)?
+            .with_forward_clients(forward_clients)
+            .with_head_events(head_tx.clone());
+        let _forwarder = spawn_forwarder(state).await;
+
+        // slot 12 is more than 2 slots ahead of the head.
+        head_tx.send(head_event(9))?;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(prewarmed.load(Ordering::Relaxed), 0);
+
+        // the same head event is received from every beacon node.
+        head_tx.send(head_event(10))?;
+        head_tx.send(head_event(10))?;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(prewarmed.load(Ordering::Relaxed), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fail_over_unreachable_preconfer() -> Result<()> {
+        let reachable = serve(
+            Router::new()
+                .route("/", post(|| async { "reachable" }))
+                .route("/health", get(|| async {})),
+        )
+        .await;
+        let unreachable = unreachable_url().await;
+        let mut lookahead = Lookahead::default();
+        for (slot, url) in [(10, &unreachable), (11, &reachable)] {
+            lookahead.insert(slot, LookaheadEntry { url: url.clone(), ..Default::default() });
+        }
+        let manager =
+            LookaheadManager::new(lookahead, LookaheadProvider::None, UrlProvider::LookaheadEntry)
+                .with_health_probe(Some(HealthProbe {
+                    path: HealthProbe::DEFAULT_PATH.into(),
+                    entries: HealthProbe::DEFAULT_ENTRIES,
+                    interval_ms: 100,
+                    timeout_ms: 100,
+                }));
+        let mut managers = HashMap::new();
+        managers.insert(1u16, manager);
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+        // the preconfers are probed.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let res = reqwest::Client::new().post(format!("{}/1", forwarder)).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await?, "reachable", "the preconfer of slot 10 is skipped");
+
+        let health: serde_json::Value =
+            reqwest::get(format!("{}/health", forwarder)).await?.json().await?;
+        assert_eq!(health["1"]["unreachable_preconfers"], json!([format!("{}/", unreachable)]));
+        Ok(())
+    }
+
+    /// Head event of the beacon node events API.
+    fn head_event(slot: u64) -> HeadEvent {
+        serde_json::from_value(json!({
+            "slot": slot.to_string(),
+            "block": B256::ZERO,
+            "state": B256::ZERO,
+            "epoch_transition": false,
+            "previous_duty_dependent_root": B256::ZERO,
+            "current_duty_dependent_root": B256::ZERO,
+            "execution_optimistic": false,
+        }))
+        .unwrap()
+    }
+
+    /// Logs the latency percentiles of `CLIENTS` clients forwarding requests concurrently to a
+    /// lookahead of two epochs: `cargo test --release -- --ignored --nocapture
+    /// bench_forward_latency`.
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn bench_forward_latency() -> Result<()> {
+        let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+        const CLIENTS: usize = 32;
+        const REQUESTS_PER_CLIENT: usize = 200;
+
+        let preconfer = spawn_echo_preconfer().await;
+        let map = Arc::new(LookaheadMap::default());
+        for slot in 0..64 {
+            map.insert(slot, LookaheadEntry { url: preconfer.clone(), ..Default::default() });
+        }
+        let mut managers = HashMap::new();
+        managers.insert(
+            1u16,
+            LookaheadManager::new(
+                Lookahead { map, ..Default::default() },
+                LookaheadProvider::None,
+                UrlProvider::LookaheadEntry,
+            ),
+        );
+        let forwarder = spawn_forwarder(SharedState::new(managers)?).await;
+        let url = format!("{}/1", forwarder);
+
+        let client = reqwest::Client::new();
+        let started = std::time::Instant::now();
+        let clients = (0..CLIENTS).map(|_| {
+            let (client, url) = (client.clone(), url.clone());
+            tokio::spawn(async move {
+                let mut latencies = Vec::with_capacity(REQUESTS_PER_CLIENT);
+                for _ in 0..REQUESTS_PER_CLIENT {
+                    let start = std::time::Instant::now();
+                    let res = client
+                        .post(&url)
+                        .header("Content-Type", "application/json")
+                        .body("dummy plain body")
+                        .send()
+                        .await
+                        .unwrap();
+                    assert_eq!(res.status(), StatusCode::OK);
+                    latencies.push(start.elapsed());
+                }
+                latencies
+            })
+        });
+        let mut latencies = vec![];
+        for latencies_of_client in futures::future::join_all(clients).await {
+            latencies.extend(latencies_of_client?);
+        }
+        let elapsed = started.elapsed();
+        assert_eq!(latencies.len(), CLIENTS * REQUESTS_PER_CLIENT);
+        latencies.sort();
+
+        let percentile = |p: usize| latencies[latencies.len() * p / 100 - 1];
+        tracing::info!(
+            target: "bench",
+            requests = latencies.len(),
+            ?elapsed,
+            p50 = ?percentile(50),
+            p99 = ?percentile(99),
+            max = ?latencies[latencies.len() - 1],
+            "forward latency",
+        );
+        Ok(())
+    }
+
+    /// Serves `router` on a free port and returns its url.
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            // The address of the client is used to rate limit its requests.
+            let app = router.into_make_service_with_connect_info::<SocketAddr>();
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    }
+
+    /// Spawns the forward service and returns its url.
+    async fn spawn_forwarder(state: SharedState) -> String {
+        serve(router(state)).await
+    }
+
+    /// Spawns a preconfer answering every request with the body it received, and returns its
+    /// url.
+    async fn spawn_echo_preconfer() -> String {
+        serve(Router::new().route("/", post(|body: Bytes| async move { body }))).await
+    }
+
+    /// Spawns a preconfer counting the plain requests it received, reported at `/cnt`, and
+    /// returns its url.
+    async fn spawn_counting_preconfer() -> String {
+        let dst = Arc::new(Mutex::new(DummySharedState { cnt: 0 }));
+        let router: Router = Router::new()
+            .route("/", post(handle_request))
+            .route("/cnt", get(counter))
+            .with_state(dst);
+        serve(router).await
+    }
+
+    /// Sends to chain 1 of the forwarder the plain request expected by the counting preconfer.
+    async fn send_plain_request(forwarder: &str) -> Result<reqwest::Response> {
+        Ok(reqwest::Client::new()
+            .post(format!("{}/1", forwarder))
+            .header("Content-Type", "application/json")
+            .body("dummy plain body")
+            .send()
+            .await?)
+    }
+
+    /// Returns the url of a port nothing listens on.
+    async fn unreachable_url() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    type WsClient = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+    /// Sends a JSON-RPC request and returns the next message, its response.
+    async fn call(ws: &mut WsClient, mut request: serde_json::Value) -> Result<serde_json::Value> {
+        request["jsonrpc"] = "2.0".into();
+        ws.send(Message::Text(request.to_string())).await?;
+        next_message(ws).await
+    }
+
+    async fn next_message(ws: &mut WsClient) -> Result<serde_json::Value> {
+        let message = ws.next().await.ok_or_else(|| eyre::eyre!("connection closed"))??;
+        Ok(serde_json::from_str(message.to_text()?)?)
+    }
+
+    /// Bundle of legacy transactions with `nonces`, signed by the user.
+    fn bundle_request(nonces: Vec<u64>) -> BundleInclusionRequest {
+        let txs = nonces
+            .into_iter()
+            .map(|nonce| {
+                let tx = Transaction::Legacy(TxLegacy {
+                    chain_id: Some(1),
+                    nonce,
+                    gas_limit: 21_000,
+                    ..Default::default()
+                });
+                TransactionSigned::from_transaction_and_signature(tx, Default::default())
+            })
+            .collect();
+        let mut bundle = BundleInclusionRequest {
+            slot: 0,
+            txs,
+            signature: Signature::from_str(&"01".repeat(65)).unwrap(),
+        };
+        let signature = sign_message(B256::repeat_byte(1), bundle.digest(1)).unwrap();
+        bundle.signature =
+            Signature::from_rs_and_parity(signature.r, signature.s, signature.odd_y_parity)
+                .unwrap();
+        bundle
+    }
+
+    fn inclusion_request(gas_limit: u64) -> InclusionRequest {
+        let tx = Transaction::Legacy(TxLegacy { gas_limit, ..Default::default() });
+        sign_request(InclusionRequest {
+            slot: 0,
+            last_slot: None,
+            deadline: None,
+            tx: TransactionSigned::from_transaction_and_signature(tx, Default::default()),
+            sidecar: None,
+            signature: Signature::from_str(&"01".repeat(65)).unwrap(),
+        })
+    }
+
+    /// Signs the request for chain 1 as the user.
+    fn sign_request(mut request: InclusionRequest) -> InclusionRequest {
+        let signature = sign_message(B256::repeat_byte(1), request.signing_hash(1)).unwrap();
+        request.signature =
+            Signature::from_rs_and_parity(signature.r, signature.s, signature.odd_y_parity)
+                .unwrap();
+        request
+    }
+
+    /// Inclusion request of a transaction with `nonce`, signed by the same sender.
+    fn signed_request(nonce: u64) -> InclusionRequest {
+        let tx = Transaction::Legacy(TxLegacy { nonce, gas_limit: 21_000, ..Default::default() });
+        let signature = sign_message(B256::repeat_byte(1), tx.signature_hash()).unwrap();
+        sign_request(InclusionRequest {
+            tx: TransactionSigned::from_transaction_and_signature(tx, signature),
+            ..inclusion_request(21_000)
+        })
+    }
+
     async fn batch_requests(port: u16, no_requests: u16) -> Result<()> {
         for _ in 0..no_requests {
             let mut headers = HeaderMap::new();
@@ -0,0 +1,13 @@
+#[derive(Debug, thiserror::Error)]
+pub enum LookaheadError {
+    #[error("no lookahead provider found")]
+    NoElectedPreconfer,
+    #[error("no preconfer elected between slots {first_slot} and {last_slot}")]
+    NoElectedPreconferInRange { first_slot: u64, last_slot: u64 },
+    #[error(
+        "inclusion request gas {requested} exceeds the remaining gas {remaining} of the preconfer elected for slot {slot}"
+    )]
+    GasLimitExceeded { slot: u64, requested: u64, remaining: u64 },
+    #[error("request arrived less than {cutoff_ms} ms before the block proposal of slot {slot}")]
+    DeadlineExceeded { slot: u64, cutoff_ms: u64 },
+}
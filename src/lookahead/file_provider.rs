@@ -0,0 +1,285 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use alloy::rpc::types::beacon::{events::HeadEvent, BlsPublicKey};
+use eyre::{Result, WrapErr};
+use hashbrown::HashMap;
+use serde::Deserialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use super::{Lookahead, LookaheadEntry};
+use crate::{
+    constants::EPOCH_SLOTS,
+    preconf::election::{PreconferElection, SignedPreconferElection},
+};
+
+/// Interval at which the schedule file is checked for changes.
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of slots after the head slot for which the rotation is added to the lookahead.
+const ROTATION_LOOKAHEAD_SLOTS: u64 = 2 * EPOCH_SLOTS;
+
+/// A preconfer in the schedule file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SchedulePreconfer {
+    pub pubkey: BlsPublicKey,
+    pub url: String,
+    #[serde(default)]
+    pub gas_limit: Option<u64>,
+}
+
+/// A preconfer elected for a specific slot in the schedule file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ScheduleSlot {
+    pub slot: u64,
+    #[serde(flatten)]
+    pub preconfer: SchedulePreconfer,
+}
+
+/// Static lookahead schedule. Preconfers of `slots` take precedence over the `rotation`, in
+/// which the preconfer of slot `n` is `rotation[n % rotation.len()]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct LookaheadSchedule {
+    #[serde(default)]
+    pub slots: Vec<ScheduleSlot>,
+    #[serde(default)]
+    pub rotation: Vec<SchedulePreconfer>,
+}
+
+impl LookaheadSchedule {
+    /// Parses a schedule in JSON if the file has a `.json` extension, in TOML otherwise.
+    pub fn parse(path: &Path, content: &str) -> Result<Self> {
+        let schedule = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(content)?,
+            _ => toml::from_str(content)?,
+        };
+        Ok(schedule)
+    }
+
+    /// Returns the preconfer elected for `slot`, if any.
+    pub fn preconfer_for_slot(&self, slot: u64) -> Option<&SchedulePreconfer> {
+        self.slots.iter().find(|s| s.slot == slot).map(|s| &s.preconfer).or_else(|| {
+            (!self.rotation.is_empty())
+                .then(|| &self.rotation[(slot % self.rotation.len() as u64) as usize])
+        })
+    }
+}
+
+/// The file lookahead provider builds the lookahead from a static schedule file, for local
+/// devnets and testing. The file is watched and the lookahead is rebuilt when it changes.
+#[derive(Debug)]
+pub struct FileLookaheadProvider {
+    lookahead: Lookahead,
+    path: PathBuf,
+    chain_id: u64,
+    /// Content of the file the `schedule` was parsed from.
+    content: String,
+    schedule: LookaheadSchedule,
+    head_slot: u64,
+}
+
+impl FileLookaheadProvider {
+    /// Creates a new `FileLookaheadProvider`, failing if the schedule file is not valid.
+    pub fn new(lookahead: Lookahead, path: PathBuf, chain_id: u64) -> Result<Self> {
+        let content = fs::read_to_string(&path)
+            .wrap_err(format!("could not read lookahead file {}", path.display()))?;
+        let schedule = LookaheadSchedule::parse(&path, &content)
+            .wrap_err(format!("could not parse lookahead file {}", path.display()))?;
+        Ok(Self { lookahead, path, chain_id, content, schedule, head_slot: 0 })
+    }
+
+    /// Runs indefinitely, updates the lookahead on new head events and when the file changes.
+    pub(super) async fn run(mut self, mut head_event_rx: broadcast::Receiver<HeadEvent>) {
+        self.update_lookahead();
+        let mut interval = tokio::time::interval(FILE_POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                head_event = head_event_rx.recv() => match head_event {
+                    Ok(head_event) => self.on_new_head_slot(head_event.slot),
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+                _ = interval.tick() => self.reload_if_changed(),
+            }
+        }
+    }
+
+    fn on_new_head_slot(&mut self, head_slot: u64) {
+        if head_slot <= self.head_slot {
+            return;
+        }
+        self.head_slot = head_slot;
+        self.lookahead.clear_slots(self.head_slot);
+        self.update_lookahead();
+    }
+
+    /// Reloads the schedule if the file content changed. An invalid file is ignored and the
+    /// previous schedule is kept.
+    fn reload_if_changed(&mut self) {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) if content != self.content => content,
+            Ok(_) => return,
+            Err(err) => {
+                warn!(target: "lookahead", path = %self.path.display(), ?err, "could not read lookahead file");
+                return;
+            }
+        };
+
+        match LookaheadSchedule::parse(&self.path, &content) {
+            Ok(schedule) => {
+                info!(target: "lookahead", path = %self.path.display(), "lookahead file reloaded");
+                self.schedule = schedule;
+                self.update_lookahead();
+            }
+            Err(err) => {
+                warn!(target: "lookahead", path = %self.path.display(), ?err, "invalid lookahead file, keeping previous schedule");
+            }
+        }
+        self.content = content;
+    }
+
+    /// Sets the lookahead from the schedule: every slot of the schedule from the head slot
+    /// onwards, and the rotation for the next `ROTATION_LOOKAHEAD_SLOTS` slots.
+    fn update_lookahead(&mut self) {
+        let mut entries = HashMap::new();
+        for slot in self.schedule.slots.iter().filter(|s| s.slot >= self.head_slot) {
+            entries.insert(slot.slot, self.entry(slot.slot, &slot.preconfer));
+        }
+        for slot in self.head_slot..self.head_slot + ROTATION_LOOKAHEAD_SLOTS {
+            if let Some(preconfer) = self.schedule.preconfer_for_slot(slot) {
+                entries.entry(slot).or_insert_with(|| self.entry(slot, preconfer));
+            }
+        }
+
+        // Removes the slots that are not part of the schedule anymore.
+        self.lookahead.retain(|slot| entries.contains_key(&slot));
+        for (slot, entry) in entries {
+            self.lookahead.insert(slot, entry);
+        }
+    }
+
+    fn entry(&self, slot: u64, preconfer: &SchedulePreconfer) -> LookaheadEntry {
+        LookaheadEntry {
+            url: preconfer.url.clone(),
+            election: SignedPreconferElection {
+                message: PreconferElection {
+                    preconfer_pubkey: preconfer.pubkey,
+                    slot_number: slot,
+                    chain_id: self.chain_id,
+                    gas_limit: preconfer.gas_limit,
+                },
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use alloy::rpc::types::beacon::constants::BLS_PUBLIC_KEY_BYTES_LEN;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    const TOML_SCHEDULE: &str = r#"
+        [[slots]]
+        slot = 5
+        pubkey = "0x2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a"
+        url = "http://preconfer-slot-5"
+        gas-limit = 30000000
+
+        [[rotation]]
+        pubkey = "0x010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101"
+        url = "http://preconfer-1"
+
+        [[rotation]]
+        pubkey = "0x020202020202020202020202020202020202020202020202020202020202020202020202020202020202020202020202"
+        url = "http://preconfer-2"
+    "#;
+
+    fn schedule_file(suffix: &str, content: &str) -> NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    fn lookahead() -> Lookahead {
+        Lookahead::default()
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let file = schedule_file(".toml", TOML_SCHEDULE);
+        let schedule =
+            LookaheadSchedule::parse(file.path(), &fs::read_to_string(file.path()).unwrap())
+                .unwrap();
+
+        let slot_5 = schedule.preconfer_for_slot(5).unwrap();
+        assert_eq!(slot_5.pubkey, BlsPublicKey::from([42u8; BLS_PUBLIC_KEY_BYTES_LEN]));
+        assert_eq!(slot_5.gas_limit, Some(30_000_000));
+        assert_eq!(schedule.preconfer_for_slot(6).unwrap().url, "http://preconfer-1");
+        assert_eq!(schedule.preconfer_for_slot(7).unwrap().url, "http://preconfer-2");
+
+        let json = format!(
+            r#"{{"slots":[{{"slot":1,"pubkey":"{}","url":"http://preconfer-slot-1"}}]}}"#,
+            BlsPublicKey::from([42u8; BLS_PUBLIC_KEY_BYTES_LEN])
+        );
+        let file = schedule_file(".json", &json);
+        let schedule = LookaheadSchedule::parse(file.path(), &json).unwrap();
+        assert_eq!(schedule.preconfer_for_slot(1).unwrap().url, "http://preconfer-slot-1");
+        assert!(schedule.preconfer_for_slot(2).is_none());
+    }
+
+    #[test]
+    fn test_fail_if_invalid_file() {
+        let file = schedule_file(".toml", "[[rotation]]\nurl = \"http://no-pubkey\"");
+        assert!(FileLookaheadProvider::new(lookahead(), file.path().to_path_buf(), 1).is_err());
+    }
+
+    #[test]
+    fn test_update_lookahead_on_new_head_slot() {
+        let file = schedule_file(".toml", TOML_SCHEDULE);
+        let lookahead = lookahead();
+        let mut provider =
+            FileLookaheadProvider::new(lookahead.clone(), file.path().to_path_buf(), 1).unwrap();
+
+        provider.on_new_head_slot(4);
+        assert_eq!(lookahead.map.len(), ROTATION_LOOKAHEAD_SLOTS as usize);
+        let entry = lookahead.get_next_elected_preconfer().unwrap();
+        assert_eq!(entry.slot(), 4);
+        assert_eq!(entry.url, "http://preconfer-1");
+        assert_eq!(entry.election.message.chain_id, 1);
+        assert_eq!(lookahead.map.get(&5).unwrap().url, "http://preconfer-slot-5");
+
+        provider.on_new_head_slot(6);
+        assert!(lookahead.map.get(&5).is_none());
+        assert_eq!(lookahead.get_next_elected_preconfer().unwrap().slot(), 6);
+    }
+
+    #[test]
+    fn test_reload_if_changed() {
+        let mut file = schedule_file(".toml", TOML_SCHEDULE);
+        let lookahead = lookahead();
+        let mut provider =
+            FileLookaheadProvider::new(lookahead.clone(), file.path().to_path_buf(), 1).unwrap();
+        provider.update_lookahead();
+        assert_eq!(lookahead.map.len(), ROTATION_LOOKAHEAD_SLOTS as usize);
+
+        // invalid files are ignored.
+        file.write_all(b"invalid").unwrap();
+        provider.reload_if_changed();
+        assert_eq!(lookahead.map.len(), ROTATION_LOOKAHEAD_SLOTS as usize);
+
+        let only_slot_5 = TOML_SCHEDULE.split("[[rotation]]").next().unwrap();
+        fs::write(file.path(), only_slot_5).unwrap();
+        provider.reload_if_changed();
+        assert_eq!(lookahead.map.len(), 1);
+        assert_eq!(lookahead.get_next_elected_preconfer().unwrap().slot(), 5);
+    }
+}
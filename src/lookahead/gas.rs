@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use serde::Serialize;
+
+use super::error::LookaheadError;
+
+/// Gas budget of an elected preconfer for a given slot. `gas_limit` and `gas_remaining` are
+/// `None` if the preconfer was elected without a gas limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GasBudget {
+    pub slot: u64,
+    pub gas_limit: Option<u64>,
+    pub gas_used: u64,
+    pub gas_remaining: Option<u64>,
+}
+
+/// Gas reserved for a forwarded inclusion request. Used to give the gas back to the budget
+/// if the request could not be forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasReservation {
+    pub slot: u64,
+    pub gas: u64,
+}
+
+/// `GasTracker` keeps track of the cumulative gas of the inclusion requests routed to the
+/// preconfer elected for each slot.
+#[derive(Debug, Clone, Default)]
+pub struct GasTracker {
+    used: Arc<DashMap<u64, u64>>,
+}
+
+impl GasTracker {
+    /// Reserves `gas` for `slot`. Fails if the total gas routed to `slot` would exceed
+    /// `gas_limit`. Without a `gas_limit` the reservation always succeeds.
+    pub fn reserve(
+        &self,
+        slot: u64,
+        gas_limit: Option<u64>,
+        gas: u64,
+    ) -> Result<GasReservation, LookaheadError> {
+        let mut used = self.used.entry(slot).or_insert(0);
+        if let Some(gas_limit) = gas_limit {
+            let remaining = gas_limit.saturating_sub(*used);
+            if gas > remaining {
+                return Err(LookaheadError::GasLimitExceeded { slot, requested: gas, remaining });
+            }
+        }
+        *used = used.saturating_add(gas);
+        Ok(GasReservation { slot, gas })
+    }
+
+    /// Gives back the gas of a reservation to the budget of its slot.
+    pub fn release(&self, reservation: GasReservation) {
+        if let Some(mut used) = self.used.get_mut(&reservation.slot) {
+            *used = used.saturating_sub(reservation.gas);
+        }
+    }
+
+    /// Returns the gas budget for `slot` given the elected `gas_limit`.
+    pub fn budget(&self, slot: u64, gas_limit: Option<u64>) -> GasBudget {
+        let gas_used = self.used.get(&slot).map(|used| *used).unwrap_or_default();
+        let gas_remaining = gas_limit.map(|gas_limit| gas_limit.saturating_sub(gas_used));
+        GasBudget { slot, gas_limit, gas_used, gas_remaining }
+    }
+
+    /// Clears the gas used for slots older than `head_slot`.
+    pub fn clear_slots(&self, head_slot: u64) {
+        self.used.retain(|slot, _| *slot >= head_slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_within_gas_limit() {
+        let tracker = GasTracker::default();
+        tracker.reserve(10, Some(50_000), 21_000).unwrap();
+        tracker.reserve(10, Some(50_000), 21_000).unwrap();
+
+        let budget = tracker.budget(10, Some(50_000));
+        assert_eq!(budget.gas_used, 42_000);
+        assert_eq!(budget.gas_remaining, Some(8_000));
+    }
+
+    #[test]
+    fn test_reserve_exceeding_gas_limit() {
+        let tracker = GasTracker::default();
+        tracker.reserve(10, Some(30_000), 21_000).unwrap();
+
+        let err = tracker.reserve(10, Some(30_000), 21_000).unwrap_err();
+        assert!(matches!(err, LookaheadError::GasLimitExceeded {
+            slot: 10,
+            requested: 21_000,
+            remaining: 9_000
+        }));
+        // other slots have their own budget.
+        tracker.reserve(11, Some(30_000), 21_000).unwrap();
+    }
+
+    #[test]
+    fn test_reserve_without_gas_limit() {
+        let tracker = GasTracker::default();
+        tracker.reserve(10, None, 30_000_000).unwrap();
+        tracker.reserve(10, None, 30_000_000).unwrap();
+
+        let budget = tracker.budget(10, None);
+        assert_eq!(budget.gas_used, 60_000_000);
+        assert_eq!(budget.gas_remaining, None);
+    }
+
+    #[test]
+    fn test_release_and_clear_slots() {
+        let tracker = GasTracker::default();
+        let reservation = tracker.reserve(10, Some(30_000), 21_000).unwrap();
+        tracker.release(reservation);
+        assert_eq!(tracker.budget(10, Some(30_000)).gas_remaining, Some(30_000));
+
+        tracker.reserve(10, Some(30_000), 21_000).unwrap();
+        tracker.clear_slots(11);
+        assert_eq!(tracker.budget(10, Some(30_000)).gas_used, 0);
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::RwLock;
+
+use hashbrown::HashSet;
+use url::Url;
+
+/// `PreconferHealth` holds the preconfers found unreachable by the last health probe, so that
+/// requests are routed to the next reachable preconfer instead of timing out.
+#[derive(Debug, Default)]
+pub struct PreconferHealth {
+    unreachable: RwLock<HashSet<Url>>,
+}
+
+impl PreconferHealth {
+    /// Returns false if the preconfer at `url` was unreachable when last probed.
+    pub fn is_reachable(&self, url: &Url) -> bool {
+        !self.unreachable.read().unwrap().contains(url)
+    }
+
+    /// Records the outcome of a probe of the preconfers: each url with whether it was reachable.
+    /// Preconfers that were not probed are considered reachable again.
+    pub fn update(&self, probes: impl IntoIterator<Item = (Url, bool)>) {
+        let unreachable =
+            probes.into_iter().filter(|(_, reachable)| !reachable).map(|(url, _)| url).collect();
+        *self.unreachable.write().unwrap() = unreachable;
+    }
+
+    /// Returns the urls of the unreachable preconfers, sorted.
+    pub fn unreachable(&self) -> Vec<Url> {
+        let mut urls: Vec<_> = self.unreachable.read().unwrap().iter().cloned().collect();
+        urls.sort();
+        urls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let (a, b) = (Url::from_str("http://a").unwrap(), Url::from_str("http://b").unwrap());
+        let health = PreconferHealth::default();
+        assert!(health.is_reachable(&a));
+
+        health.update([(a.clone(), false), (b.clone(), true)]);
+        assert!(!health.is_reachable(&a));
+        assert!(health.is_reachable(&b));
+        assert_eq!(health.unreachable(), vec![a.clone()]);
+
+        // `a` is not among the next preconfers anymore.
+        health.update([(b.clone(), true)]);
+        assert!(health.is_reachable(&a));
+    }
+}
@@ -1,17 +1,27 @@
-use std::str::FromStr;
+use std::{
+    ops::RangeInclusive,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use alloy::rpc::types::beacon::{events::HeadEvent, BlsPublicKey};
-use dashmap::DashMap;
 use eyre::{bail, ContextCompat, Result, WrapErr};
 use hashbrown::HashMap;
 use tokio::sync::broadcast;
+use tracing::warn;
 use url::Url;
 
 use super::{
-    provider::LookaheadProvider, Lookahead, LookaheadEntry, LookaheadProviderOptions,
-    RelayLookaheadProvider,
+    error::LookaheadError, provider::LookaheadProvider, FileLookaheadProvider, GasBudget,
+    GasReservation, Lookahead, LookaheadChange, LookaheadEntry, LookaheadProviderOptions,
+    PreconferHealth, RelayLookaheadProvider,
+};
+use crate::{
+    config::{Config, Deadline, DeadlineAction, Fallback, HealthProbe},
+    metrics::PRECONFER_FAILOVERS,
+    relay_client::{RelayClient, RelayHealthSnapshot},
+    validation::TxValidator,
 };
-use crate::config::Config;
 
 #[derive(Debug)]
 /// Manages the state of the lookahead provider.
@@ -32,14 +42,32 @@ pub struct LookaheadManager {
     lookahead: Lookahead,
     provider_manager: Option<LookaheadProviderManager>,
     url_provider: UrlProvider,
+    /// Relays used by the lookahead provider, kept to report their health.
+    relays: Vec<RelayClient>,
+    /// Behaviour of the forwarder when no preconfer is elected.
+    fallback: Option<Fallback>,
+    /// Cutoff before the block proposal of the slot of the elected preconfer.
+    deadline: Option<Deadline>,
+    /// Validates the transactions of inclusion requests before they are forwarded.
+    validator: Option<TxValidator>,
+    /// Probes the health of the next elected preconfers.
+    health_probe: Option<HealthProbe>,
+    /// Preconfers found unreachable by the health probe, skipped when routing requests.
+    preconfer_health: PreconferHealth,
 }
 
 impl Default for LookaheadManager {
     fn default() -> Self {
         Self {
-            lookahead: Lookahead { map: DashMap::new().into() },
+            lookahead: Lookahead::default(),
             provider_manager: Some(LookaheadProviderManager::Initialized(LookaheadProvider::None)),
             url_provider: UrlProvider::LookaheadEntry,
+            relays: Vec::new(),
+            fallback: None,
+            deadline: None,
+            validator: None,
+            health_probe: None,
+            preconfer_health: PreconferHealth::default(),
         }
     }
 }
@@ -52,11 +80,56 @@ impl LookaheadManager {
     ) -> Self {
         Self {
             lookahead,
+            relays: lookahead_provider.relays(),
             provider_manager: Some(LookaheadProviderManager::Initialized(lookahead_provider)),
             url_provider,
+            fallback: None,
+            deadline: None,
+            validator: None,
+            health_probe: None,
+            preconfer_health: PreconferHealth::default(),
         }
     }
 
+    /// Sets the behaviour of the forwarder when no preconfer is elected.
+    pub fn with_fallback(mut self, fallback: Option<Fallback>) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    pub fn fallback(&self) -> Option<&Fallback> {
+        self.fallback.as_ref()
+    }
+
+    /// Sets the cutoff before the block proposal after which requests are rejected or rerouted.
+    pub fn with_deadline(mut self, deadline: Option<Deadline>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Sets the validator of the transactions of inclusion requests.
+    pub fn with_validator(mut self, validator: Option<TxValidator>) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    pub fn validator(&self) -> Option<&TxValidator> {
+        self.validator.as_ref()
+    }
+
+    pub fn with_health_probe(mut self, health_probe: Option<HealthProbe>) -> Self {
+        self.health_probe = health_probe;
+        self
+    }
+
+    pub fn health_probe(&self) -> Option<&HealthProbe> {
+        self.health_probe.as_ref()
+    }
+
+    pub fn preconfer_health(&self) -> &PreconferHealth {
+        &self.preconfer_health
+    }
+
     /// Runs the lookahead provider in a separate thread.
     /// It returns an error if the provider is already running.
     pub fn run_provider(&mut self) -> Result<()> {
@@ -74,53 +147,232 @@ impl LookaheadManager {
         }
     }
 
-    fn get_next_elected_preconfer(&self) -> Option<LookaheadEntry> {
-        self.lookahead.get_next_elected_preconfer()
+    /// Returns the next elected preconfer that can still honor a request given the deadline.
+    fn get_next_elected_preconfer(&self) -> Result<LookaheadEntry, LookaheadError> {
+        let entry = self
+            .lookahead
+            .get_next_elected_preconfer()
+            .ok_or(LookaheadError::NoElectedPreconfer)?;
+        self.apply_deadline(entry).map(|entry| self.fail_over(entry))
+    }
+
+    /// Returns `entry`, or the first preconfer elected after it that was not found unreachable
+    /// by the health probe. `entry` is kept if all the preconfers after it are unreachable.
+    fn fail_over(&self, entry: LookaheadEntry) -> LookaheadEntry {
+        if self.is_reachable(&entry) {
+            return entry;
+        }
+        let mut next = self.lookahead.get_elected_preconfer_for_slot(entry.slot() + 1);
+        while let Some(candidate) = next {
+            if self.is_reachable(&candidate) {
+                PRECONFER_FAILOVERS.inc();
+                return candidate;
+            }
+            next = self.lookahead.get_elected_preconfer_for_slot(candidate.slot() + 1);
+        }
+        entry
+    }
+
+    fn is_reachable(&self, entry: &LookaheadEntry) -> bool {
+        self.url_for_entry(entry).map_or(true, |url| self.preconfer_health.is_reachable(&url))
+    }
+
+    /// Rejects or reroutes to the preconfer elected after `entry` if the request arrived within
+    /// the cutoff before the block proposal of `entry`.
+    fn apply_deadline(&self, entry: LookaheadEntry) -> Result<LookaheadEntry, LookaheadError> {
+        let Some(deadline) = &self.deadline else { return Ok(entry) };
+        let now_ms = unix_time_ms();
+        if !deadline.is_past_cutoff(entry.slot(), now_ms) {
+            return Ok(entry);
+        }
+
+        let exceeded =
+            LookaheadError::DeadlineExceeded { slot: entry.slot(), cutoff_ms: deadline.cutoff_ms };
+        match deadline.action {
+            DeadlineAction::Reject => Err(exceeded),
+            DeadlineAction::Reroute => self
+                .lookahead
+                .get_elected_preconfer_for_slot(entry.slot() + 1)
+                .filter(|next| !deadline.is_past_cutoff(next.slot(), now_ms))
+                .ok_or(exceeded),
+        }
     }
 
     pub fn get_url(&self) -> Result<Url> {
-        match self.get_next_elected_preconfer() {
-            None => bail!("no lookahead provider found"),
-            Some(entry) => match &self.url_provider {
-                UrlProvider::LookaheadEntry => {
-                    Ok(Url::from_str(&entry.url).wrap_err("not a valid url")?)
+        let entry = self.get_next_elected_preconfer()?;
+        self.url_for_entry(&entry)
+    }
+
+    /// Returns the url of the preconfer responsible for `slot` and reserves `gas` from the gas
+    /// limit it was elected with. It returns an error if the preconfer has not enough gas left.
+    pub fn reserve_gas_for_slot(&self, slot: u64, gas: u64) -> Result<(Url, GasReservation)> {
+        let entry = self
+            .lookahead
+            .get_elected_preconfer_for_slot(slot)
+            .ok_or(LookaheadError::NoElectedPreconfer)?;
+        let entry = self.fail_over(self.apply_deadline(entry)?);
+        let url = self.url_for_entry(&entry)?;
+        let reservation =
+            self.lookahead.gas_tracker.reserve(entry.slot(), entry.election.gas_limit(), gas)?;
+        Ok((url, reservation))
+    }
+
+    /// Returns the url of the earliest preconfer elected in `slots` that can still honor the
+    /// request given the deadline and has `gas` left, and reserves `gas` from its budget.
+    pub fn reserve_gas_in_range(
+        &self,
+        slots: RangeInclusive<u64>,
+        gas: u64,
+    ) -> Result<(Url, GasReservation)> {
+        let now_ms = unix_time_ms();
+        let mut last_err = eyre::Report::new(LookaheadError::NoElectedPreconferInRange {
+            first_slot: *slots.start(),
+            last_slot: *slots.end(),
+        });
+        // Unreachable preconfers are only tried if no reachable one can honor the request.
+        let mut entries = self.lookahead.get_elected_preconfers_in_range(slots);
+        entries.sort_by_key(|entry| !self.is_reachable(entry));
+        for entry in entries {
+            if let Some(deadline) = &self.deadline {
+                if deadline.is_past_cutoff(entry.slot(), now_ms) {
+                    last_err = LookaheadError::DeadlineExceeded {
+                        slot: entry.slot(),
+                        cutoff_ms: deadline.cutoff_ms,
+                    }
+                    .into();
+                    continue;
                 }
-                UrlProvider::UrlMap(m) => {
-                    let pub_key = entry.election.preconfer_pubkey();
-                    m.get(&pub_key)
-                        .cloned()
-                        .wrap_err(format!("could not find key for pubkey {}", pub_key))
+            }
+            let url = self.url_for_entry(&entry)?;
+            match self.lookahead.gas_tracker.reserve(entry.slot(), entry.election.gas_limit(), gas)
+            {
+                Ok(reservation) => return Ok((url, reservation)),
+                Err(err) => last_err = err.into(),
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Returns the latest head slot of the chain, 0 if it is not known yet.
+    pub fn head_slot(&self) -> u64 {
+        self.lookahead.head_slot()
+    }
+
+    /// Returns the slot of the last preconfer elected in the lookahead.
+    pub fn last_elected_slot(&self) -> Option<u64> {
+        self.lookahead.map.last_slot()
+    }
+
+    /// Subscribes to the changes of the lookahead.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<LookaheadChange> {
+        self.lookahead.subscribe_changes()
+    }
+
+    /// Gives back reserved gas, e.g. when the request could not be forwarded.
+    pub fn release_gas(&self, reservation: GasReservation) {
+        self.lookahead.gas_tracker.release(reservation);
+    }
+
+    /// Returns the gas budget of the next elected preconfer.
+    pub fn gas_budget(&self) -> Result<GasBudget> {
+        let entry = self.get_next_elected_preconfer()?;
+        Ok(self.lookahead.gas_tracker.budget(entry.slot(), entry.election.gas_limit()))
+    }
+
+    /// Returns the health of the relays the lookahead is fetched from.
+    pub fn relay_health(&self) -> Vec<RelayHealthSnapshot> {
+        self.relays.iter().map(RelayClient::health).collect()
+    }
+
+    /// Returns the distinct urls of the preconfers elected for the slots of `slots`.
+    pub fn preconfer_urls_in_range(&self, slots: RangeInclusive<u64>) -> Vec<Url> {
+        self.distinct_urls(self.lookahead.get_elected_preconfers_in_range(slots))
+    }
+
+    /// Returns the distinct urls of the next `count` elected preconfers.
+    pub fn next_preconfer_urls(&self, count: usize) -> Vec<Url> {
+        self.distinct_urls(self.lookahead.get_next_elected_preconfers(count))
+    }
+
+    fn distinct_urls(&self, entries: Vec<LookaheadEntry>) -> Vec<Url> {
+        let mut urls = vec![];
+        for entry in entries {
+            match self.url_for_entry(&entry) {
+                Ok(url) if !urls.contains(&url) => urls.push(url),
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(target: "lookahead", slot = entry.slot(), ?err, "no preconfer url")
                 }
-            },
+            }
+        }
+        urls
+    }
+
+    fn url_for_entry(&self, entry: &LookaheadEntry) -> Result<Url> {
+        match &self.url_provider {
+            UrlProvider::LookaheadEntry => {
+                Ok(Url::from_str(&entry.url).wrap_err("not a valid url")?)
+            }
+            UrlProvider::UrlMap(m) => {
+                let pub_key = entry.election.preconfer_pubkey();
+                m.get(&pub_key)
+                    .cloned()
+                    .wrap_err(format!("could not find key for pubkey {}", pub_key))
+            }
         }
     }
 }
 
+pub(crate) fn unix_time_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
 /// BBuilds a map of lookahead managers from the configuration, keyed by the chain-id.
 pub fn lookahead_managers_from_config(
     config: Config,
     beacon_tx: broadcast::Sender<HeadEvent>,
-) -> HashMap<u16, LookaheadManager> {
-    // build managers from relay lookahead providers
+) -> Result<HashMap<u16, LookaheadManager>> {
+    // build managers from relay or file lookahead providers
     let mut map = HashMap::new();
     for r_c in config.lookaheads {
-        let lookahead = Lookahead { map: DashMap::new().into() };
-        let provider = LookaheadProviderOptions {
-            head_event_receiver: Some(beacon_tx.subscribe()),
-            relay_provider: Some(RelayLookaheadProvider::new(
-                lookahead.clone(),
-                r_c.relays,
-                HashMap::new(),
-            )),
-        }
-        .build_relay_provider();
+        let lookahead = Lookahead::default();
+        let provider = match r_c.lookahead_file {
+            Some(path) => LookaheadProviderOptions {
+                head_event_receiver: Some(beacon_tx.subscribe()),
+                file_provider: Some(FileLookaheadProvider::new(
+                    lookahead.clone(),
+                    path,
+                    r_c.chain_id.into(),
+                )?),
+                ..Default::default()
+            }
+            .build_file_provider(),
+            None => LookaheadProviderOptions {
+                head_event_receiver: Some(beacon_tx.subscribe()),
+                relay_provider: Some(RelayLookaheadProvider::new(
+                    lookahead.clone(),
+                    r_c.relays.into_iter().map(Into::into).collect(),
+                    HashMap::new(),
+                    r_c.schedule,
+                )),
+                ..Default::default()
+            }
+            .build_relay_provider(),
+        };
         let url_provider = match r_c.provider {
             crate::config::Provider::Lookahead => UrlProvider::LookaheadEntry,
             crate::config::Provider::Registry => {
                 UrlProvider::UrlMap(r_c.registry.expect("registry is empty"))
             }
         };
-        map.insert(r_c.chain_id, LookaheadManager::new(lookahead, provider, url_provider));
+        map.insert(
+            r_c.chain_id,
+            LookaheadManager::new(lookahead, provider, url_provider)
+                .with_fallback(r_c.fallback)
+                .with_deadline(r_c.deadline)
+                .with_validator(r_c.validation.map(Into::into))
+                .with_health_probe(r_c.health_probe),
+        );
     }
-    map
+    Ok(map)
 }
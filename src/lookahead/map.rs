@@ -0,0 +1,163 @@
+use std::{
+    collections::BTreeMap,
+    ops::RangeInclusive,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use super::LookaheadEntry;
+
+/// `LookaheadMap` is a concurrent map of elections ordered by slot. Finding the next election,
+/// the first one at or after a slot, or the ones in a range of slots is O(log n), and readers
+/// only block while an election is inserted or removed.
+#[derive(Debug, Default)]
+pub struct LookaheadMap {
+    entries: RwLock<BTreeMap<u64, LookaheadEntry>>,
+}
+
+impl LookaheadMap {
+    /// Inserts the election of `slot`, returns the election it replaced if any.
+    pub fn insert(&self, slot: u64, entry: LookaheadEntry) -> Option<LookaheadEntry> {
+        self.write().insert(slot, entry)
+    }
+
+    pub fn get(&self, slot: &u64) -> Option<LookaheadEntry> {
+        self.read().get(slot).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Keeps the elections for which `keep` returns true.
+    pub fn retain(&self, keep: impl FnMut(&u64, &mut LookaheadEntry) -> bool) {
+        self.write().retain(keep);
+    }
+
+    /// Returns the election with the lowest slot.
+    pub fn first(&self) -> Option<LookaheadEntry> {
+        self.read().first_key_value().map(|(_, entry)| entry.clone())
+    }
+
+    /// Returns the first election at or after `slot`.
+    pub fn first_at_or_after(&self, slot: u64) -> Option<LookaheadEntry> {
+        self.read().range(slot..).next().map(|(_, entry)| entry.clone())
+    }
+
+    /// Returns the first `count` elections, in slot order.
+    pub fn first_n(&self, count: usize) -> Vec<LookaheadEntry> {
+        self.read().values().take(count).cloned().collect()
+    }
+
+    /// Returns the elections in `slots`, in slot order.
+    pub fn range(&self, slots: RangeInclusive<u64>) -> Vec<LookaheadEntry> {
+        // `BTreeMap::range` panics on inverted ranges.
+        if slots.is_empty() {
+            return Vec::new();
+        }
+        self.read().range(slots).map(|(_, entry)| entry.clone()).collect()
+    }
+
+    /// Returns the highest slot with an election.
+    pub fn last_slot(&self) -> Option<u64> {
+        self.read().last_key_value().map(|(slot, _)| *slot)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<u64, LookaheadEntry>> {
+        self.entries.read().unwrap()
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BTreeMap<u64, LookaheadEntry>> {
+        self.entries.write().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    fn entry(slot: u64) -> LookaheadEntry {
+        LookaheadEntry { url: format!("http://preconfer-{}", slot), ..Default::default() }
+    }
+
+    #[test]
+    fn test_ordered_queries() {
+        let map = LookaheadMap::default();
+        for slot in [12, 5, 9, 20] {
+            map.insert(slot, entry(slot));
+        }
+        assert_eq!(map.first(), Some(entry(5)));
+        assert_eq!(map.first_at_or_after(6), Some(entry(9)));
+        assert_eq!(map.first_at_or_after(9), Some(entry(9)));
+        assert_eq!(map.first_at_or_after(21), None);
+        assert_eq!(map.range(6..=20), vec![entry(9), entry(12), entry(20)]);
+        assert_eq!(map.range(20..=6), vec![]);
+        assert_eq!(map.first_n(2), vec![entry(5), entry(9)]);
+        assert_eq!(map.last_slot(), Some(20));
+
+        map.retain(|slot, _| *slot >= 10);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.first(), Some(entry(12)));
+    }
+
+    /// Logs the time per call of the lookahead queries of the forward path while another thread
+    /// keeps sliding the lookahead: `cargo test --release -- --ignored --nocapture
+    /// bench_queries_under_load`.
+    #[test]
+    #[ignore]
+    fn bench_queries_under_load() {
+        let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+        const SLOTS: u64 = 4 * 32;
+        const ITERATIONS: u32 = 1_000_000;
+
+        let map = Arc::new(LookaheadMap::default());
+        for slot in 0..SLOTS {
+            map.insert(slot, entry(slot));
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (map, done) = (map.clone(), done.clone());
+            std::thread::spawn(move || {
+                let mut slot = SLOTS;
+                while !done.load(Ordering::Relaxed) {
+                    map.insert(slot, entry(slot));
+                    map.retain(|s, _| *s + SLOTS > slot);
+                    slot += 1;
+                    std::thread::sleep(Duration::from_micros(100));
+                }
+            })
+        };
+
+        let bench = |name: &str, query: &dyn Fn(u64)| {
+            let start = Instant::now();
+            for i in 0..ITERATIONS {
+                query(i as u64 % SLOTS);
+            }
+            let per_op = start.elapsed() / ITERATIONS;
+            tracing::info!(target: "bench", query = name, ?per_op, "lookahead query latency");
+        };
+        bench("first", &|_| {
+            map.first();
+        });
+        bench("first_at_or_after", &|slot| {
+            map.first_at_or_after(slot);
+        });
+        bench("range", &|slot| {
+            map.range(slot..=slot + 2);
+        });
+
+        done.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+    }
+}
@@ -1,17 +1,33 @@
-use std::sync::Arc;
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
-use dashmap::DashMap;
+use serde::Serialize;
+use tokio::sync::broadcast;
 
 use crate::preconf::election::SignedPreconferElection;
 
+pub(crate) mod error;
+mod file_provider;
+mod gas;
+mod health;
 mod manager;
+mod map;
 mod provider;
 
+pub use file_provider::*;
+pub use gas::*;
+pub use health::*;
 pub use manager::*;
+pub use map::*;
 pub use provider::*;
 
 /// Wraps a signed election and url.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct LookaheadEntry {
     pub url: String,
     pub election: SignedPreconferElection,
@@ -23,27 +39,214 @@ impl LookaheadEntry {
     }
 }
 
+/// Change of the lookahead, published to the subscribers of `Lookahead::subscribe_changes`.
+/// Serialized with the kind of change as `change`, without the entries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum LookaheadChange {
+    /// An election was inserted at a slot that had none.
+    Inserted {
+        slot: u64,
+        #[serde(skip)]
+        entry: LookaheadEntry,
+    },
+    /// The election of a slot was replaced by a different one.
+    Replaced {
+        slot: u64,
+        #[serde(skip)]
+        previous: LookaheadEntry,
+        #[serde(skip)]
+        entry: LookaheadEntry,
+    },
+    /// The elections of `slots` were cleared when the head moved to `head_slot`.
+    Expired { head_slot: u64, slots: Vec<u64> },
+    /// The elections of `slots` were removed from the schedule before their slot.
+    Removed { slots: Vec<u64> },
+}
+
+impl LookaheadChange {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Inserted { .. } => "inserted",
+            Self::Replaced { .. } => "replaced",
+            Self::Expired { .. } => "expired",
+            Self::Removed { .. } => "removed",
+        }
+    }
+
+    /// Returns the slot of an inserted or replaced election.
+    pub fn elected_slot(&self) -> Option<u64> {
+        match self {
+            Self::Inserted { slot, .. } | Self::Replaced { slot, .. } => Some(*slot),
+            Self::Expired { .. } | Self::Removed { .. } => None,
+        }
+    }
+}
+
+/// Capacity of the channel notifying the changes of the lookahead.
+const CHANGES_CHANNEL_SIZE: usize = 256;
+
 #[derive(Debug, Clone)]
 /// `Lookahead` manages a map of upcoming electors keyed by their election slot.
 pub struct Lookahead {
-    pub map: Arc<DashMap<u64, LookaheadEntry>>,
+    pub map: Arc<LookaheadMap>,
+    /// Notifies every change of the lookahead.
+    pub(crate) changes_tx: broadcast::Sender<LookaheadChange>,
+    /// Gas routed to the preconfer elected for each slot, cleared with the lookahead.
+    pub(crate) gas_tracker: GasTracker,
+    /// Latest head slot the lookahead was cleared at.
+    pub(crate) head_slot: Arc<AtomicU64>,
+}
+
+impl Default for Lookahead {
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+            changes_tx: broadcast::channel(CHANGES_CHANNEL_SIZE).0,
+            gas_tracker: Default::default(),
+            head_slot: Default::default(),
+        }
+    }
 }
 
 impl Lookahead {
-    /// Clears slots in the lookahead that are older than `head_slot`.
+    /// Clears slots in the lookahead, and the gas routed to them, that are older than
+    /// `head_slot`.
     pub fn clear_slots(&mut self, head_slot: u64) {
-        self.map.retain(|slot, _| *slot >= head_slot);
+        self.gas_tracker.clear_slots(head_slot);
+        self.head_slot.fetch_max(head_slot, Ordering::Relaxed);
+        let slots = self.remove_slots(|slot| slot >= head_slot);
+        if !slots.is_empty() {
+            self.notify(LookaheadChange::Expired { head_slot, slots });
+        }
+    }
+
+    /// Removes the elections whose slot does not satisfy `keep`, e.g. when they are not part
+    /// of the schedule anymore.
+    pub fn retain(&mut self, keep: impl Fn(u64) -> bool) {
+        let slots = self.remove_slots(keep);
+        if !slots.is_empty() {
+            self.notify(LookaheadChange::Removed { slots });
+        }
+    }
+
+    /// Returns the latest head slot, 0 if no head event was received yet.
+    pub fn head_slot(&self) -> u64 {
+        self.head_slot.load(Ordering::Relaxed)
     }
 
-    pub fn insert(&mut self, election_slot: u64, slot: LookaheadEntry) {
-        self.map.insert(election_slot, slot);
+    /// Inserts the election of `election_slot`. Inserting the same election again is not
+    /// notified as a change.
+    pub fn insert(&mut self, election_slot: u64, entry: LookaheadEntry) {
+        let change = match self.map.insert(election_slot, entry.clone()) {
+            None => LookaheadChange::Inserted { slot: election_slot, entry },
+            Some(previous) if previous != entry => {
+                LookaheadChange::Replaced { slot: election_slot, previous, entry }
+            }
+            Some(_) => return,
+        };
+        self.notify(change);
     }
+
+    /// Subscribes to the changes of the lookahead.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<LookaheadChange> {
+        self.changes_tx.subscribe()
+    }
+
+    /// Removes the elections whose slot does not satisfy `keep` and returns their slots, sorted.
+    fn remove_slots(&self, keep: impl Fn(u64) -> bool) -> Vec<u64> {
+        let mut slots = vec![];
+        self.map.retain(|slot, _| {
+            if keep(*slot) {
+                return true;
+            }
+            slots.push(*slot);
+            false
+        });
+        slots.sort_unstable();
+        slots
+    }
+
+    fn notify(&self, change: LookaheadChange) {
+        // There may be no subscribers.
+        let _ = self.changes_tx.send(change);
+    }
+
     /// Returns the next preconfer. If there is no preconfer elected for the current slot,
     /// it will return the next known election. Or None, if there are no elected preconfers in
     /// the next epoch.
     /// Any elected preconfers older than `head_slot` will have been cleared so, we fetch this by
     /// getting the preconfer with the lowest slot number.
     pub fn get_next_elected_preconfer(&self) -> Option<LookaheadEntry> {
-        self.map.iter().min_by_key(|entry| entry.slot()).map(|entry| entry.value().clone())
+        self.map.first()
+    }
+
+    /// Returns the next `count` elected preconfers, in slot order.
+    pub fn get_next_elected_preconfers(&self, count: usize) -> Vec<LookaheadEntry> {
+        self.map.first_n(count)
+    }
+
+    /// Returns the first preconfer elected at or after `slot`, i.e. the preconfer responsible
+    /// for including transactions at `slot`. None if it is not known yet.
+    pub fn get_elected_preconfer_for_slot(&self, slot: u64) -> Option<LookaheadEntry> {
+        self.map.first_at_or_after(slot)
+    }
+
+    /// Returns the preconfers elected for the slots of `slots`, in slot order.
+    pub fn get_elected_preconfers_in_range(
+        &self,
+        slots: RangeInclusive<u64>,
+    ) -> Vec<LookaheadEntry> {
+        self.map.range(slots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str) -> LookaheadEntry {
+        LookaheadEntry { url: url.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut lookahead = Lookahead::default();
+        let mut changes_rx = lookahead.subscribe_changes();
+
+        lookahead.insert(10, entry("a"));
+        lookahead.insert(10, entry("a"));
+        lookahead.insert(10, entry("b"));
+        lookahead.insert(11, entry("a"));
+        lookahead.insert(12, entry("a"));
+        lookahead.clear_slots(10);
+        lookahead.clear_slots(12);
+        lookahead.retain(|slot| slot != 12);
+
+        let changes: Vec<_> = std::iter::from_fn(|| changes_rx.try_recv().ok()).collect();
+        assert_eq!(changes, vec![
+            LookaheadChange::Inserted { slot: 10, entry: entry("a") },
+            LookaheadChange::Replaced { slot: 10, previous: entry("a"), entry: entry("b") },
+            LookaheadChange::Inserted { slot: 11, entry: entry("a") },
+            LookaheadChange::Inserted { slot: 12, entry: entry("a") },
+            LookaheadChange::Expired { head_slot: 12, slots: vec![10, 11] },
+            LookaheadChange::Removed { slots: vec![12] },
+        ]);
+        assert_eq!(
+            serde_json::to_value(&changes[4]).unwrap(),
+            serde_json::json!({ "change": "expired", "head_slot": 12, "slots": [10, 11] })
+        );
+    }
+
+    #[test]
+    fn test_clear_slots_prunes_gas() {
+        let mut lookahead = Lookahead::default();
+        lookahead.gas_tracker.reserve(10, Some(30_000), 21_000).unwrap();
+        lookahead.gas_tracker.reserve(12, Some(30_000), 21_000).unwrap();
+
+        // the gas of past slots is cleared even if they had no election.
+        lookahead.clear_slots(11);
+        assert_eq!(lookahead.gas_tracker.budget(10, Some(30_000)).gas_used, 0);
+        assert_eq!(lookahead.gas_tracker.budget(12, Some(30_000)).gas_used, 21_000);
     }
 }
@@ -1,33 +1,83 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 use alloy::rpc::types::beacon::{events::HeadEvent, BlsPublicKey};
 use futures::future::join_all;
 use hashbrown::HashMap;
-use tokio::sync::broadcast::{self, Receiver};
-use tracing::{debug, info};
+use tokio::sync::broadcast::{self, error::RecvError, Receiver};
+use tracing::{debug, info, warn};
 
-use super::Lookahead;
+use super::{FileLookaheadProvider, Lookahead};
 use crate::{
+    config::FetchSchedule,
     constants::EPOCH_SLOTS,
     lookahead::LookaheadEntry,
     preconf::election::SignedPreconferElection,
-    relay_client::{RelayClient, RelayClientConfig},
+    relay_client::{error::RelayClientError, RelayClient, RelayClientConfig},
 };
 
-#[derive(Debug)]
+/// Capacity of the channel receiving the elections streamed by relays.
+const ELECTION_CHANNEL_SIZE: usize = 64;
+
+#[derive(Debug, Default)]
 struct LookaheadContext {
     /// Current slot of the `LookaheadProvider`
     head_slot: u64,
     /// Latest epoch of lookaheads that have been set.
-    /// This ensures we only set the lookahead once per epoch.
-    curr_lookahead_epoch: u64,
+    /// The current epoch is fetched as well if its lookahead has never been set, e.g. on startup.
+    curr_lookahead_epoch: Option<u64>,
+    /// Head slot at which the lookahead was last fetched.
+    /// This ensures we only fetch the lookahead once per scheduled slot.
+    last_fetch_slot: Option<u64>,
+}
+
+impl LookaheadContext {
+    /// Returns the epochs to fetch the lookahead for at `head_slot` according to `schedule`, or an
+    /// empty list if no fetch is due.
+    fn epochs_to_fetch(&self, schedule: &FetchSchedule, head_slot: u64) -> Vec<u64> {
+        let curr_epoch = head_slot / EPOCH_SLOTS;
+        let curr_epoch_start_slot = curr_epoch * EPOCH_SLOTS;
+
+        // Latest scheduled slot reached in the current epoch. Due if we have not fetched since,
+        // which also covers scheduled slots missed because no head event was received.
+        let scheduled = schedule
+            .slots_into_epoch
+            .iter()
+            .rev()
+            .map(|slot| curr_epoch_start_slot + slot)
+            .find(|slot| *slot <= head_slot)
+            .is_some_and(|slot| !matches!(self.last_fetch_slot, Some(last) if last >= slot));
+        let refetch = match (schedule.refetch_interval, self.last_fetch_slot) {
+            (Some(interval), Some(last)) => head_slot >= last + interval,
+            _ => false,
+        };
+        if !scheduled && !refetch {
+            return Vec::new();
+        }
+
+        let mut epochs = Vec::with_capacity(2);
+        if schedule.fetch_current_epoch ||
+            !matches!(self.curr_lookahead_epoch, Some(epoch) if epoch >= curr_epoch)
+        {
+            epochs.push(curr_epoch);
+        }
+        if schedule.fetch_next_epoch {
+            epochs.push(curr_epoch + 1);
+        }
+        epochs
+    }
+
+    /// Records that the lookahead of `epochs` has been fetched at `head_slot`.
+    fn on_lookahead_fetched(&mut self, head_slot: u64, epochs: &[u64]) {
+        self.last_fetch_slot = Some(head_slot);
+        self.curr_lookahead_epoch = self.curr_lookahead_epoch.max(epochs.iter().max().copied());
+    }
 }
 
 #[derive(Debug)]
 /// The relay lookahead provider keeps track of the lookahead, i.e. the slot -> preconfer map.
 /// It builds this progressively by querying relays for preconfers for a given slot.
-/// Preconf lookahead is guaranteed at epoch time. So we fetch for epoch + 1 at slot > 1 in the
-/// current epoch.
+/// Preconf lookahead is guaranteed at epoch time. So by default we fetch for epoch + 1 at slot 1
+/// in the current epoch, see `FetchSchedule`.
 pub struct RelayLookaheadProvider {
     /// Maps a slot to the elected preconfer for that slot.
     lookahead: Lookahead,
@@ -36,40 +86,75 @@ pub struct RelayLookaheadProvider {
     /// List of relay URLs that support the constraints API. Preconfers will be fetched
     /// from these relays.
     relays: Vec<RelayClient>,
+    /// Defines when the lookahead is fetched.
+    schedule: FetchSchedule,
     context: LookaheadContext,
+    /// Maps a slot to the weight of the relay its election was received from.
+    election_weights: BTreeMap<u64, u32>,
 }
 
 impl RelayLookaheadProvider {
     /// Creates a new `LookaheadProvider` with the given relays.
     pub fn new(
         lookahead: Lookahead,
-        relay_urls: Vec<String>,
+        relay_configs: Vec<RelayClientConfig>,
         preconfer_registry: HashMap<BlsPublicKey, String>,
+        schedule: FetchSchedule,
     ) -> Self {
-        let relays = relay_urls
-            .into_iter()
-            .map(|url| {
-                let config = RelayClientConfig::new(url, true);
-                RelayClient::new(config.into())
-            })
-            .collect();
+        let mut relays: Vec<RelayClient> =
+            relay_configs.into_iter().map(|config| RelayClient::new(config.into())).collect();
+        // Results are applied in this order, so elections from the relays with the highest
+        // weight are applied last and overwrite the others.
+        relays.sort_by_key(|relay| relay.weight());
 
         Self {
             lookahead,
             preconfer_registry,
             relays,
-            context: LookaheadContext { head_slot: 0, curr_lookahead_epoch: 0 },
+            schedule,
+            context: LookaheadContext::default(),
+            election_weights: BTreeMap::new(),
         }
     }
 
     /// Runs indefinitely, subscribes to new head events.
     /// At set times, determines which preconfers have been elected for each slot in the next epoch.
+    /// Elections streamed by relays supporting subscriptions are added as soon as they are
+    /// received, polling acts as a fallback.
     async fn run(mut self, mut head_event_rx: broadcast::Receiver<HeadEvent>) {
-        while let Ok(head_event) = head_event_rx.recv().await {
-            self.on_new_head_event(head_event).await;
+        let (election_tx, mut election_rx) = broadcast::channel(ELECTION_CHANNEL_SIZE);
+        for relay in self.relays.iter().filter(|relay| relay.subscribe_enabled()) {
+            let relay = relay.clone();
+            let election_tx = election_tx.clone();
+            tokio::spawn(async move {
+                relay.subscribe_to_elections(election_tx).await;
+            });
+        }
+
+        loop {
+            tokio::select! {
+                head_event = head_event_rx.recv() => match head_event {
+                    Ok(head_event) => self.on_new_head_event(head_event).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(target: "lookahead", skipped, "lagging behind head events");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                Ok((weight, election)) = election_rx.recv() => self.on_new_election(election, weight),
+            }
         }
     }
 
+    /// Adds an election streamed by a relay of `weight` to the lookahead, unless it is for a past
+    /// slot.
+    fn on_new_election(&mut self, election: SignedPreconferElection, weight: u32) {
+        if election.slot() < self.head_slot() {
+            debug!(target: "lookahead", election_slot = election.slot(), "ignoring election for past slot");
+            return;
+        }
+        self.add_elected_preconfer_to_lookahead(election, weight);
+    }
+
     /// Updates the local context's slot and cleans up any out-of-date entries in the lookahead.
     /// If the slot meets the right conditions, it will fetch the lookahead for a new epoch.
     async fn on_new_head_event(&mut self, head_event: HeadEvent) {
@@ -84,27 +169,26 @@ impl RelayLookaheadProvider {
 
         // Clear lookahead of old slots.
         self.lookahead.clear_slots(head_slot);
+        self.election_weights = self.election_weights.split_off(&head_slot);
 
-        // Only query each epoch once.
-        // if self.curr_lookahead_epoch() > curr_epoch {
-        //     return;
-        // }
+        let epochs = self.context.epochs_to_fetch(&self.schedule, head_slot);
+        if epochs.is_empty() {
+            return;
+        }
 
-        // Make sure we are at least 20 slots in. Often when querying duties on the epoch boundary
-        // the values are incorrect, so waiting an extra slot fixes this.
-        if self.head_slot() % 6 == 0 {
-            let curr_epoch_start_slot = curr_epoch * EPOCH_SLOTS;
-            info!(target: "lookahead", head_slot, curr_epoch_start_slot, "fetching preconfer lookahead");
+        let curr_epoch_start_slot = curr_epoch * EPOCH_SLOTS;
+        info!(target: "lookahead", head_slot, curr_epoch_start_slot, ?epochs, "fetching preconfer lookahead");
 
-            // Fetch and update the lookahead
-            self.fetch_preconfer_lookahead(curr_epoch + 1).await;
+        // Fetch and update the lookahead
+        for epoch in epochs.iter() {
+            self.fetch_preconfer_lookahead(*epoch).await;
         }
+        self.context.on_lookahead_fetched(head_slot, &epochs);
     }
 
     /// For a given epoch, fetch the elected preconfers from all relays and add results
-    /// to the lookahead.
-    ///
-    /// Sets the `context.curr_lookahead_epoch` to `epoch` at the end.
+    /// to the lookahead. If relays disagree on the preconfer of a slot, the election from the
+    /// relay with the highest weight is kept.
     async fn fetch_preconfer_lookahead(&mut self, epoch: u64) {
         let epoch_start_slot = epoch * EPOCH_SLOTS;
         info!(target: "lookahead", %epoch, %epoch_start_slot, "fetching preconfer elections for epoch");
@@ -113,33 +197,45 @@ impl RelayLookaheadProvider {
         for relay in self.relays.iter() {
             lookahead_handles.push(relay.get_elected_preconfers_for_epoch(epoch));
         }
+        let weights: Vec<u32> = self.relays.iter().map(RelayClient::weight).collect();
 
-        for result in join_all(lookahead_handles).await {
+        for (weight, result) in weights.into_iter().zip(join_all(lookahead_handles).await) {
             match result {
                 Ok(Some(preconfer_elections)) => {
                     for election in preconfer_elections {
-                        self.add_elected_preconfer_to_lookahead(election);
+                        self.add_elected_preconfer_to_lookahead(election, weight);
                     }
                 }
                 Ok(None) => {
                     debug!(target: "lookahead", epoch, "no elected preconfers found");
                 }
+                Err(RelayClientError::CircuitOpen) => {
+                    debug!(target: "lookahead", epoch, "relay skipped, circuit breaker is open");
+                }
                 Err(error) => {
-                    debug!(?error, "failed to fetch elected preconfer");
+                    warn!(target: "lookahead", ?error, "failed to fetch elected preconfer");
                 }
             }
         }
-
-        self.set_curr_lookahead_epoch(epoch);
     }
 
-    /// Adds a new election to our lookahead. Will overwrite any existing elected preconfer for that
-    /// slot.
-    fn add_elected_preconfer_to_lookahead(&mut self, election: SignedPreconferElection) {
+    /// Adds a new election from a relay of `weight` to our lookahead. Will overwrite the elected
+    /// preconfer for that slot, unless it was received from a relay with a higher weight.
+    fn add_elected_preconfer_to_lookahead(
+        &mut self,
+        election: SignedPreconferElection,
+        weight: u32,
+    ) {
+        let election_slot = election.slot();
+        if self.election_weights.get(&election_slot).is_some_and(|current| *current > weight) {
+            debug!(target: "lookahead", %election_slot, weight, "ignoring election from a relay with a lower weight");
+            return;
+        }
+        self.election_weights.insert(election_slot, weight);
+
         let preconfer_url =
             self.preconfer_registry.get(&election.preconfer_pubkey()).cloned().unwrap_or_default();
 
-        let election_slot = election.slot();
         debug!(
             target: "lookahead",
             %election_slot,
@@ -152,6 +248,11 @@ impl RelayLookaheadProvider {
         self.lookahead.insert(election_slot, entry);
     }
 
+    /// Returns the relays the lookahead is fetched from.
+    pub fn relays(&self) -> &[RelayClient] {
+        &self.relays
+    }
+
     /// Returns the current head slot.
     fn head_slot(&self) -> u64 {
         self.context.head_slot
@@ -161,21 +262,12 @@ impl RelayLookaheadProvider {
     fn set_head_slot(&mut self, slot: u64) {
         self.context.head_slot = slot;
     }
-
-    // /// Returns the current lookahead epoch.
-    // fn curr_lookahead_epoch(&self) -> u64 {
-    //     self.context.curr_lookahead_epoch
-    // }
-
-    /// Sets the current lookahead epoch.
-    fn set_curr_lookahead_epoch(&mut self, epoch: u64) {
-        self.context.curr_lookahead_epoch = epoch;
-    }
 }
 
 #[derive(Default)]
 pub struct LookaheadProviderOptions {
     pub relay_provider: Option<RelayLookaheadProvider>,
+    pub file_provider: Option<FileLookaheadProvider>,
     pub head_event_receiver: Option<Receiver<HeadEvent>>,
 }
 
@@ -190,6 +282,17 @@ impl LookaheadProviderOptions {
                 .expect("head event receiver is mandatory to build relay provider"),
         }
     }
+
+    pub fn build_file_provider(self) -> LookaheadProvider {
+        LookaheadProvider::File {
+            provider: self
+                .file_provider
+                .expect("file provider is mandatory to build file provider"),
+            receiver: self
+                .head_event_receiver
+                .expect("head event receiver is mandatory to build file provider"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -200,16 +303,30 @@ pub enum LookaheadProvider {
         provider: RelayLookaheadProvider,
         receiver: Receiver<HeadEvent>,
     },
+    /// Reads the lookahead from a static schedule file, see `FileLookaheadProvider`.
+    File {
+        provider: FileLookaheadProvider,
+        receiver: Receiver<HeadEvent>,
+    },
     #[allow(dead_code)]
     /// Used for testing purposes, `LookaheadProvider::None` does not fetch any lookahead.
     None,
 }
 
 impl LookaheadProvider {
+    /// Returns the relays used by the provider, if any.
+    pub fn relays(&self) -> Vec<RelayClient> {
+        match self {
+            LookaheadProvider::Relay { provider, .. } => provider.relays().to_vec(),
+            LookaheadProvider::File { .. } | LookaheadProvider::None => Vec::new(),
+        }
+    }
+
     /// Runs the lookahead provider and waits for execution to finish.
     pub async fn run(self) {
         match self {
             LookaheadProvider::Relay { provider, receiver } => provider.run(receiver).await,
+            LookaheadProvider::File { provider, receiver } => provider.run(receiver).await,
             LookaheadProvider::None => LookaheadProvider::wait().await,
         };
     }
@@ -220,3 +337,93 @@ impl LookaheadProvider {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preconf::election::PreconferElection;
+
+    /// Simulates head events for `slots` and returns the head slots at which the lookahead was
+    /// fetched, with the fetched epochs.
+    fn simulate(
+        schedule: &FetchSchedule,
+        slots: impl Iterator<Item = u64>,
+    ) -> Vec<(u64, Vec<u64>)> {
+        let mut context = LookaheadContext::default();
+        let mut fetches = Vec::new();
+        for slot in slots {
+            let epochs = context.epochs_to_fetch(schedule, slot);
+            if !epochs.is_empty() {
+                context.on_lookahead_fetched(slot, &epochs);
+                fetches.push((slot, epochs));
+            }
+        }
+        fetches
+    }
+
+    #[test]
+    fn test_default_schedule_fetches_once_per_epoch() {
+        let fetches = simulate(&FetchSchedule::default(), 32..128);
+        // on startup the current epoch is fetched as well.
+        assert_eq!(fetches, vec![(33, vec![1, 2]), (65, vec![3]), (97, vec![4])]);
+    }
+
+    #[test]
+    fn test_missed_scheduled_slot_is_fetched_on_next_head() {
+        let fetches = simulate(&FetchSchedule::default(), [32, 40, 41, 64, 70, 71].into_iter());
+        assert_eq!(fetches, vec![(40, vec![1, 2]), (70, vec![3])]);
+    }
+
+    #[test]
+    fn test_multiple_slots_into_epoch() {
+        let schedule = FetchSchedule {
+            slots_into_epoch: vec![1, 16],
+            fetch_current_epoch: true,
+            ..Default::default()
+        };
+        let fetches = simulate(&schedule, 32..96);
+        assert_eq!(fetches, vec![
+            (33, vec![1, 2]),
+            (48, vec![1, 2]),
+            (65, vec![2, 3]),
+            (80, vec![2, 3])
+        ]);
+    }
+
+    #[test]
+    fn test_refetch_interval() {
+        let schedule = FetchSchedule { refetch_interval: Some(12), ..Default::default() };
+        let fetches = simulate(&schedule, 33..70);
+        assert_eq!(fetches, vec![(33, vec![1, 2]), (45, vec![2]), (57, vec![2]), (65, vec![3])]);
+    }
+
+    #[test]
+    fn test_elections_keep_highest_relay_weight() {
+        let lookahead = Lookahead::default();
+        let mut provider = RelayLookaheadProvider::new(
+            lookahead.clone(),
+            Vec::new(),
+            HashMap::new(),
+            FetchSchedule::default(),
+        );
+        let election = |gas_limit| SignedPreconferElection {
+            message: PreconferElection {
+                slot_number: 40,
+                gas_limit: Some(gas_limit),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let elected_gas_limit =
+            || lookahead.map.first_at_or_after(40).and_then(|entry| entry.election.gas_limit());
+
+        provider.on_new_election(election(1), 2);
+        // streamed by a relay with a lower weight.
+        provider.on_new_election(election(2), 1);
+        assert_eq!(elected_gas_limit(), Some(1));
+        provider.on_new_election(election(3), 2);
+        assert_eq!(elected_gas_limit(), Some(3));
+        provider.on_new_election(election(4), 3);
+        assert_eq!(elected_gas_limit(), Some(4));
+    }
+}
@@ -7,16 +7,27 @@ use forward_service::{RpcForward, SharedState};
 use tokio::sync::broadcast;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-use crate::{config::Config, lookahead::lookahead_managers_from_config};
+use crate::{
+    auth::ApiKeys, config::Config, forward_client::forward_clients_from_config,
+    lookahead::lookahead_managers_from_config,
+};
 
+mod auth;
 mod common;
 mod config;
 mod constants;
+mod forward_client;
 mod forward_service;
 mod lookahead;
+mod metrics;
 mod preconf;
+mod queue;
+mod rate_limit;
 mod relay_client;
+mod replay;
 mod ssz;
+mod validation;
+mod ws;
 
 #[derive(Debug, Parser)]
 #[command(name = "preconf-rpc")]
@@ -49,10 +60,16 @@ async fn main() -> Result<()> {
             client.subscribe_to_head_events(beacon_tx.clone()).await;
             let listening_addr = format!("0.0.0.0:{}", port.unwrap_or(8000));
 
-            let managers = lookahead_managers_from_config(config, beacon_tx);
-            let join_handle = RpcForward::new(SharedState::new(managers)?, listening_addr)
-                .start_service()
-                .await?;
+            let api_keys = ApiKeys::new(config.api_keys.clone());
+            let rate_limits = config.rate_limit.clone().map(Into::into).unwrap_or_default();
+            let forward_clients = forward_clients_from_config(&config)?;
+            let managers = lookahead_managers_from_config(config, beacon_tx.clone())?;
+            let shared_state = SharedState::new(managers)?
+                .with_api_keys(api_keys)
+                .with_rate_limits(rate_limits)
+                .with_forward_clients(forward_clients)
+                .with_head_events(beacon_tx);
+            let join_handle = RpcForward::new(shared_state, listening_addr).start_service().await?;
             join_handle.await??;
         }
     }
@@ -0,0 +1,63 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+
+lazy_static! {
+    /// Requests sent to relays, labeled by relay name and outcome.
+    pub static ref RELAY_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "relay_requests_total",
+        "Number of requests sent to relays",
+        &["relay", "status"]
+    )
+    .unwrap();
+    /// Latency of successful relay requests, in seconds.
+    pub static ref RELAY_LATENCY: HistogramVec = register_histogram_vec!(
+        "relay_request_duration_seconds",
+        "Latency of successful relay requests",
+        &["relay"]
+    )
+    .unwrap();
+    /// 1 if the circuit breaker of the relay is open, 0 otherwise.
+    pub static ref RELAY_CIRCUIT_OPEN: IntGaugeVec = register_int_gauge_vec!(
+        "relay_circuit_open",
+        "Whether the circuit breaker of the relay is open",
+        &["relay"]
+    )
+    .unwrap();
+    /// Requests authenticated with an API key, labeled by key name and outcome.
+    pub static ref API_KEY_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "api_key_requests_total",
+        "Number of requests authenticated with an API key",
+        &["key", "status"]
+    )
+    .unwrap();
+    /// Requests rejected by a rate limit, labeled by limit (`ip` or `sender`).
+    pub static ref RATE_LIMITED_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "rate_limited_requests_total",
+        "Number of requests rejected by a rate limit",
+        &["limit"]
+    )
+    .unwrap();
+    /// Health probes of the next elected preconfers, labeled by outcome.
+    pub static ref PRECONFER_PROBES: IntCounterVec = register_int_counter_vec!(
+        "preconfer_probes_total",
+        "Number of health probes sent to preconfers",
+        &["status"]
+    )
+    .unwrap();
+    /// Requests routed away from an unreachable preconfer.
+    pub static ref PRECONFER_FAILOVERS: IntCounter = register_int_counter!(
+        "preconfer_failovers_total",
+        "Number of requests routed away from an unreachable preconfer"
+    )
+    .unwrap();
+}
+
+/// Encodes all registered metrics in the prometheus text format.
+pub fn encode() -> eyre::Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
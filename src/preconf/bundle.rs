@@ -0,0 +1,263 @@
+use alloy::primitives::{Address, Signature, B256};
+use hashbrown::HashSet;
+use reth_primitives::{TransactionSigned, TxType};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use ssz::{Decode, Encode, SszDecoderBuilder, SszEncoder, BYTES_PER_LENGTH_OFFSET};
+use ssz_types::{typenum::U65, FixedVector, VariableList};
+
+use super::{
+    commitments::{deserialize_from_str, signature_as_str},
+    eip712,
+    error::BundleError,
+};
+use crate::ssz::{MaxTransactionsPerPayload, SszTransaction};
+
+/// Maximum number of transactions in a bundle.
+pub const MAX_BUNDLE_TXS: usize = 16;
+
+type SszTransactions = VariableList<SszTransaction, MaxTransactionsPerPayload>;
+type SszSignature = FixedVector<u8, U65>;
+
+/// Request to include an ordered list of transactions at a specific slot, atomically: either
+/// all transactions are included in order, or none is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleInclusionRequest {
+    /// The consensus slot number at which the transactions should be included.
+    pub slot: u64,
+    /// The transactions to be included, in order.
+    pub txs: Vec<TransactionSigned>,
+    /// The EIP-712 signature of the bundle by the user, see `digest`.
+    pub signature: Signature,
+}
+
+impl BundleInclusionRequest {
+    /// EIP-712 hash signed by the user, of the slot and the ordered transaction hashes of the
+    /// bundle in the domain of `chain_id`. Binding the chain prevents the signed bundle from
+    /// being replayed on another chain.
+    pub fn digest(&self, chain_id: u64) -> B256 {
+        eip712::bundle_signing_hash(self, chain_id)
+    }
+
+    /// Recovers the address of the user that signed the bundle for `chain_id`.
+    pub fn recover_signer(&self, chain_id: u64) -> Result<Address, BundleError> {
+        self.signature
+            .recover_address_from_prehash(&self.digest(chain_id))
+            .map_err(|_| BundleError::InvalidSignature)
+    }
+
+    /// Total gas limit of the transactions of the bundle.
+    pub fn gas_limit(&self) -> u64 {
+        self.txs.iter().map(|tx| tx.gas_limit()).sum()
+    }
+
+    /// Validates the bundle sent to `chain_id`: it has between 1 and `MAX_BUNDLE_TXS` distinct
+    /// non-blob transactions of `chain_id`, and a valid signature for it.
+    pub fn validate(&self, chain_id: u64) -> Result<(), BundleError> {
+        if self.txs.is_empty() {
+            return Err(BundleError::Empty);
+        }
+        if self.txs.len() > MAX_BUNDLE_TXS {
+            return Err(BundleError::TooManyTransactions {
+                count: self.txs.len(),
+                max: MAX_BUNDLE_TXS,
+            });
+        }
+
+        let mut hashes = HashSet::new();
+        for tx in self.txs.iter() {
+            if !hashes.insert(tx.hash()) {
+                return Err(BundleError::DuplicateTransaction(tx.hash()));
+            }
+            if tx.tx_type() == TxType::Eip4844 {
+                return Err(BundleError::BlobTransaction);
+            }
+            if tx.chain_id() != self.txs[0].chain_id() {
+                return Err(BundleError::ChainIdMismatch);
+            }
+        }
+        if self.txs[0].chain_id() != Some(chain_id) {
+            return Err(BundleError::WrongChainId(chain_id));
+        }
+
+        self.recover_signer(chain_id)?;
+        Ok(())
+    }
+
+    /// SSZ encoding of the bundle, as the container
+    /// `{ slot: uint64, txs: List[Transaction, MAX_TRANSACTIONS_PER_PAYLOAD], signature:
+    /// ByteVector[65] }` with the transactions in their enveloped encoding.
+    pub fn as_ssz_bytes(&self) -> Vec<u8> {
+        let txs: SszTransactions = self
+            .txs
+            .iter()
+            .map(|tx| {
+                let mut encoded_tx = Vec::new();
+                tx.encode_enveloped(&mut encoded_tx);
+                SszTransaction::from(encoded_tx)
+            })
+            .collect::<Vec<_>>()
+            .into();
+        let signature = SszSignature::from(self.signature.as_bytes().to_vec());
+
+        let fixed_len = <u64 as Encode>::ssz_fixed_len() +
+            BYTES_PER_LENGTH_OFFSET +
+            <SszSignature as Encode>::ssz_fixed_len();
+        let mut buf = Vec::new();
+        let mut encoder = SszEncoder::container(&mut buf, fixed_len);
+        encoder.append(&self.slot);
+        encoder.append(&txs);
+        encoder.append(&signature);
+        encoder.finalize();
+        buf
+    }
+
+    pub fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, BundleError> {
+        let ssz_error = |err| BundleError::Ssz(format!("{:?}", err));
+
+        let mut builder = SszDecoderBuilder::new(bytes);
+        builder.register_type::<u64>().map_err(ssz_error)?;
+        builder.register_type::<SszTransactions>().map_err(ssz_error)?;
+        builder.register_type::<SszSignature>().map_err(ssz_error)?;
+        let mut decoder = builder.build().map_err(ssz_error)?;
+
+        let slot = decoder.decode_next::<u64>().map_err(ssz_error)?;
+        let txs = decoder
+            .decode_next::<SszTransactions>()
+            .map_err(ssz_error)?
+            .iter()
+            .map(|tx| TransactionSigned::decode_enveloped(&mut tx.to_vec().as_slice()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| BundleError::Ssz(err.to_string()))?;
+        let signature = decoder.decode_next::<SszSignature>().map_err(ssz_error)?;
+        let signature =
+            Signature::try_from(&signature[..]).map_err(|_| BundleError::InvalidSignature)?;
+
+        Ok(Self { slot, txs, signature })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleInclusionRequestHelper {
+    slot: u64,
+    txs: Vec<String>,
+    #[serde(deserialize_with = "deserialize_from_str", serialize_with = "signature_as_str")]
+    signature: Signature,
+}
+
+impl<'de> Deserialize<'de> for BundleInclusionRequest {
+    fn deserialize<D>(deserializer: D) -> Result<BundleInclusionRequest, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let helper = BundleInclusionRequestHelper::deserialize(deserializer)?;
+        let txs = helper
+            .txs
+            .iter()
+            .map(|tx| {
+                let data =
+                    alloy::hex::decode(tx.trim_start_matches("0x")).map_err(de::Error::custom)?;
+                TransactionSigned::decode_enveloped(&mut data.as_slice()).map_err(de::Error::custom)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(BundleInclusionRequest { slot: helper.slot, txs, signature: helper.signature })
+    }
+}
+
+impl Serialize for BundleInclusionRequest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let txs = self
+            .txs
+            .iter()
+            .map(|tx| {
+                let mut data = Vec::new();
+                tx.encode_enveloped(&mut data);
+                format!("0x{}", alloy::hex::encode(&data))
+            })
+            .collect();
+        BundleInclusionRequestHelper { slot: self.slot, txs, signature: self.signature }
+            .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use reth_primitives::{sign_message, Transaction, TxEip1559, TxEip4844};
+
+    use super::*;
+
+    const USER_KEY: B256 = B256::repeat_byte(1);
+
+    fn tx(nonce: u64) -> TransactionSigned {
+        let tx = Transaction::Eip1559(TxEip1559 {
+            chain_id: 1,
+            nonce,
+            gas_limit: 21_000,
+            ..Default::default()
+        });
+        TransactionSigned::from_transaction_and_signature(tx, Default::default())
+    }
+
+    fn signed_bundle(txs: Vec<TransactionSigned>) -> BundleInclusionRequest {
+        let mut bundle = BundleInclusionRequest {
+            slot: 10,
+            txs,
+            signature: Signature::from_str(&"01".repeat(65)).unwrap(),
+        };
+        let signature = sign_message(USER_KEY, bundle.digest(1)).unwrap();
+        bundle.signature =
+            Signature::from_rs_and_parity(signature.r, signature.s, signature.odd_y_parity)
+                .unwrap();
+        bundle
+    }
+
+    #[test]
+    fn test_bundle_encodings() {
+        let bundle = signed_bundle(vec![tx(0), tx(1)]);
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert_eq!(serde_json::from_str::<BundleInclusionRequest>(&json).unwrap(), bundle);
+
+        let ssz = bundle.as_ssz_bytes();
+        assert_eq!(BundleInclusionRequest::from_ssz_bytes(&ssz).unwrap(), bundle);
+        assert!(BundleInclusionRequest::from_ssz_bytes(&ssz[..ssz.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_validate_bundle() {
+        let bundle = signed_bundle(vec![tx(0), tx(1)]);
+        bundle.validate(1).unwrap();
+        assert_eq!(bundle.gas_limit(), 42_000);
+
+        // reordering the transactions invalidates the signature.
+        let reordered = BundleInclusionRequest { txs: vec![tx(1), tx(0)], ..bundle.clone() };
+        assert_ne!(reordered.recover_signer(1).ok(), bundle.recover_signer(1).ok());
+
+        // the signature is bound to the chain.
+        assert_ne!(bundle.digest(1), bundle.digest(2));
+        assert!(matches!(bundle.validate(2), Err(BundleError::WrongChainId(2))));
+
+        assert!(matches!(signed_bundle(vec![]).validate(1), Err(BundleError::Empty)));
+        assert!(matches!(
+            signed_bundle((0..=MAX_BUNDLE_TXS as u64).map(tx).collect()).validate(1),
+            Err(BundleError::TooManyTransactions { .. })
+        ));
+        assert!(matches!(
+            signed_bundle(vec![tx(0), tx(0)]).validate(1),
+            Err(BundleError::DuplicateTransaction(_))
+        ));
+
+        let blob_tx = TransactionSigned::from_transaction_and_signature(
+            Transaction::Eip4844(TxEip4844 { chain_id: 1, ..Default::default() }),
+            Default::default(),
+        );
+        assert!(matches!(
+            signed_bundle(vec![tx(0), blob_tx]).validate(1),
+            Err(BundleError::BlobTransaction)
+        ));
+    }
+}
@@ -1,43 +1,198 @@
 use std::str::FromStr;
 
-use alloy::{primitives::Signature, rpc::types::beacon::BlsSignature};
-use reth_primitives::TransactionSigned;
-use serde::{de, Deserialize, Deserializer, Serialize};
+use alloy::{
+    primitives::{Address, Signature, B256},
+    rpc::types::beacon::BlsSignature,
+    signers::Signer,
+};
+use reth_primitives::{
+    constants::eip4844::MAINNET_KZG_TRUSTED_SETUP, BlobTransaction, BlobTransactionSidecar,
+    PooledTransactionsElement, Transaction, TransactionSigned,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{eip712, error::InclusionRequestError};
+use crate::constants::EPOCH_SLOTS;
+
+/// Maximum number of slots of the slot range of an inclusion request, the lookahead spans at
+/// most two epochs.
+pub const MAX_SLOT_RANGE: u64 = 2 * EPOCH_SLOTS;
 
 /// Request to include a transaction at a specific slot
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InclusionRequest {
-    /// The consensus slot number at which the transaction should be included.
+    /// The consensus slot number at which the transaction should be included. With a
+    /// `last_slot`, the first slot of the range the transaction can be included in.
     pub slot: u64,
+    /// The last slot, inclusive, at which the transaction can be included. The request is
+    /// routed to the earliest preconfer elected in `slot..=last_slot`.
+    pub last_slot: Option<u64>,
     /// The transaction to be included.
-    #[serde(deserialize_with = "deserialize_tx_signed", serialize_with = "serialize_tx_signed")]
     pub tx: TransactionSigned,
-    /// The signature over the "slot" and "tx" fields by the user.
+    /// Unix timestamp, in seconds, after which the request must not be forwarded anymore.
+    pub deadline: Option<u64>,
+    /// The blobs of `tx`, if it is a blob transaction. In JSON, blob transactions are in their
+    /// network encoding, which carries the sidecar.
+    pub sidecar: Option<BlobTransactionSidecar>,
+    /// The EIP-712 signature of the request by the user, see `signing_hash`.
     /// A valid signature is the only proof that the user actually requested
     /// this specific commitment to be included at the given slot.
-    #[serde(deserialize_with = "deserialize_from_str", serialize_with = "signature_as_str")]
     pub signature: Signature,
 }
 
-fn deserialize_tx_signed<'de, D>(deserializer: D) -> Result<TransactionSigned, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s = String::deserialize(deserializer)?;
-    let data = alloy::hex::decode(s.trim_start_matches("0x")).map_err(de::Error::custom)?;
-    TransactionSigned::decode_enveloped(&mut data.as_slice()).map_err(de::Error::custom)
+impl InclusionRequest {
+    /// EIP-712 hash signed by the user, of the slot range, transaction hash and deadline of the
+    /// request in the domain of `chain_id`. Binding the range prevents it from being widened by
+    /// a third party.
+    pub fn signing_hash(&self, chain_id: u64) -> B256 {
+        eip712::signing_hash(self, chain_id)
+    }
+
+    /// Signs the request for `chain_id` with `signer`.
+    pub async fn sign<S: Signer + ?Sized>(
+        &mut self,
+        chain_id: u64,
+        signer: &S,
+    ) -> alloy::signers::Result<()> {
+        self.signature = signer.sign_hash(&self.signing_hash(chain_id)).await?;
+        Ok(())
+    }
+
+    /// Verifies the signature of the request for `chain_id` and returns the address of the user
+    /// that signed it.
+    pub fn verify(&self, chain_id: u64) -> Result<Address, InclusionRequestError> {
+        self.signature
+            .recover_address_from_prehash(&self.signing_hash(chain_id))
+            .map_err(|_| InclusionRequestError::InvalidSignature)
+    }
+
+    /// Validates that the deadline of the request, if any, is not before `now`, in seconds.
+    pub fn validate_deadline(&self, now: u64) -> Result<(), InclusionRequestError> {
+        match self.deadline {
+            Some(deadline) if deadline < now => {
+                Err(InclusionRequestError::DeadlinePassed { deadline })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the slots at which the transaction can be included.
+    pub fn slots(&self) -> std::ops::RangeInclusive<u64> {
+        self.slot..=self.last_slot.unwrap_or(self.slot)
+    }
+
+    /// Validates that the slot range is not empty and spans at most `MAX_SLOT_RANGE` slots.
+    pub fn validate_slot_range(&self) -> Result<(), InclusionRequestError> {
+        let Some(last_slot) = self.last_slot else { return Ok(()) };
+        if last_slot < self.slot || last_slot - self.slot >= MAX_SLOT_RANGE {
+            return Err(InclusionRequestError::InvalidSlotRange {
+                slot: self.slot,
+                last_slot,
+                max: MAX_SLOT_RANGE,
+            });
+        }
+        Ok(())
+    }
+
+    /// Validates that blob transactions carry a sidecar matching their versioned hashes, with
+    /// valid KZG proofs.
+    pub fn validate_blobs(&self) -> Result<(), InclusionRequestError> {
+        match (&self.tx.transaction, &self.sidecar) {
+            (Transaction::Eip4844(tx), Some(sidecar)) => {
+                if tx.blob_versioned_hashes.len() != sidecar.blobs.len() {
+                    return Err(InclusionRequestError::InvalidSidecar(format!(
+                        "{} versioned hashes but {} blobs",
+                        tx.blob_versioned_hashes.len(),
+                        sidecar.blobs.len()
+                    )));
+                }
+                tx.validate_blob(sidecar, &MAINNET_KZG_TRUSTED_SETUP)
+                    .map_err(|err| InclusionRequestError::InvalidSidecar(err.to_string()))
+            }
+            (Transaction::Eip4844(_), None) => Err(InclusionRequestError::MissingSidecar),
+            (_, Some(_)) => Err(InclusionRequestError::InvalidSidecar(
+                "sidecar of a non-blob transaction".to_string(),
+            )),
+            (_, None) => Ok(()),
+        }
+    }
 }
 
-fn serialize_tx_signed<S>(tx: &TransactionSigned, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
+#[derive(Serialize, Deserialize)]
+struct InclusionRequestHelper {
+    slot: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_slot: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline: Option<u64>,
+    tx: String,
+    #[serde(deserialize_with = "deserialize_from_str", serialize_with = "signature_as_str")]
+    signature: Signature,
+}
+
+impl<'de> Deserialize<'de> for InclusionRequest {
+    fn deserialize<D>(deserializer: D) -> Result<InclusionRequest, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let helper = InclusionRequestHelper::deserialize(deserializer)?;
+        let data =
+            alloy::hex::decode(helper.tx.trim_start_matches("0x")).map_err(de::Error::custom)?;
+        let (tx, sidecar) = decode_network_tx(&data).map_err(de::Error::custom)?;
+        Ok(InclusionRequest {
+            slot: helper.slot,
+            last_slot: helper.last_slot,
+            deadline: helper.deadline,
+            tx,
+            sidecar,
+            signature: helper.signature,
+        })
+    }
+}
+
+impl Serialize for InclusionRequest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let data = encode_network_tx(&self.tx, self.sidecar.as_ref());
+        InclusionRequestHelper {
+            slot: self.slot,
+            last_slot: self.last_slot,
+            deadline: self.deadline,
+            tx: format!("0x{}", alloy::hex::encode(&data)),
+            signature: self.signature,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Decodes a transaction in its network encoding, in which blob transactions carry their
+/// sidecar. Other transactions have the same network and enveloped encodings.
+fn decode_network_tx(
+    mut data: &[u8],
+) -> alloy::rlp::Result<(TransactionSigned, Option<BlobTransactionSidecar>)> {
+    match PooledTransactionsElement::decode_enveloped(&mut data)? {
+        PooledTransactionsElement::BlobTransaction(blob_tx) => {
+            let (tx, sidecar) = blob_tx.into_parts();
+            Ok((tx, Some(sidecar)))
+        }
+        element => Ok((element.into_transaction(), None)),
+    }
+}
+
+fn encode_network_tx(tx: &TransactionSigned, sidecar: Option<&BlobTransactionSidecar>) -> Vec<u8> {
     let mut data = Vec::new();
-    tx.encode_enveloped(&mut data);
-    serializer.serialize_str(&format!("0x{}", alloy::hex::encode(&data)))
+    match sidecar.map(|sidecar| BlobTransaction::try_from_signed(tx.clone(), sidecar.clone())) {
+        Some(Ok(blob_tx)) => {
+            PooledTransactionsElement::BlobTransaction(blob_tx).encode_enveloped(&mut data)
+        }
+        _ => tx.encode_enveloped(&mut data),
+    }
+    data
 }
 
-fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+pub(super) fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
     T: FromStr,
@@ -47,15 +202,12 @@ where
     T::from_str(s.trim_start_matches("0x")).map_err(de::Error::custom)
 }
 
-fn signature_as_str<S: serde::Serializer>(
+pub(super) fn signature_as_str<S: serde::Serializer>(
     sig: &Signature,
     serializer: S,
 ) -> Result<S::Ok, S::Error> {
-    let parity = sig.v();
-    // As bytes encodes the parity as 27/28, need to change that.
-    let mut bytes = sig.as_bytes();
-    bytes[bytes.len() - 1] = if parity.y_parity() { 1 } else { 0 };
-    serializer.serialize_str(&format!("0x{}", alloy::hex::encode(bytes)))
+    // `r || s || v` with `v` as 27/28, as returned by `eth_signTypedData_v4`.
+    serializer.serialize_str(&format!("0x{}", alloy::hex::encode(sig.as_bytes())))
 }
 
 // TODO
@@ -64,3 +216,197 @@ pub struct InclusionReponse {
     pub signature: BlsSignature,
     pub message: InclusionRequest,
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy::signers::local::PrivateKeySigner;
+    use reth_primitives::{
+        kzg::{Blob, Bytes48, KzgCommitment, KzgProof, BYTES_PER_BLOB},
+        kzg_to_versioned_hash, TxEip4844, TxLegacy,
+    };
+
+    use super::*;
+    use crate::preconf::constraints::InclusionConstraint;
+
+    fn blob_request(versioned_hashes: usize) -> InclusionRequest {
+        let tx = Transaction::Eip4844(TxEip4844 {
+            chain_id: 1,
+            gas_limit: 21_000,
+            blob_versioned_hashes: vec![B256::repeat_byte(1); versioned_hashes],
+            max_fee_per_blob_gas: 1,
+            ..Default::default()
+        });
+        InclusionRequest {
+            slot: 10,
+            last_slot: None,
+            deadline: None,
+            tx: TransactionSigned::from_transaction_and_signature(tx, Default::default()),
+            sidecar: Some(BlobTransactionSidecar::default()),
+            signature: Signature::from_str(&"01".repeat(65)).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_blob_request_roundtrip() {
+        let request = blob_request(0);
+        let json = serde_json::to_string(&request).unwrap();
+        let decoded: InclusionRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, request);
+        decoded.validate_blobs().unwrap();
+
+        // the sidecar is only carried by blob transactions.
+        let tx = Transaction::Legacy(TxLegacy::default());
+        let request = InclusionRequest {
+            tx: TransactionSigned::from_transaction_and_signature(tx, Default::default()),
+            sidecar: None,
+            ..request
+        };
+        let decoded: InclusionRequest =
+            serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+        assert_eq!(decoded.sidecar, None);
+        decoded.validate_blobs().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_blob_sidecar() {
+        // the sidecar has no blob for the versioned hash.
+        let request = blob_request(1);
+        assert!(matches!(request.validate_blobs(), Err(InclusionRequestError::InvalidSidecar(_))));
+
+        let request = InclusionRequest { sidecar: None, ..blob_request(0) };
+        assert!(matches!(request.validate_blobs(), Err(InclusionRequestError::MissingSidecar)));
+    }
+
+    /// Blob request with a single blob, and its KZG commitment and proof computed with the
+    /// mainnet trusted setup.
+    fn kzg_blob_request() -> (InclusionRequest, Bytes48, Bytes48) {
+        // every field element of the blob is lower than the BLS modulus.
+        let mut data = [0u8; BYTES_PER_BLOB];
+        for (i, element) in data.chunks_mut(32).enumerate() {
+            element[31] = i as u8;
+        }
+        let blob = Blob::new(data);
+        let commitment = KzgCommitment::blob_to_kzg_commitment(&blob, &MAINNET_KZG_TRUSTED_SETUP)
+            .unwrap()
+            .to_bytes();
+        let proof =
+            KzgProof::compute_blob_kzg_proof(&blob, &commitment, &MAINNET_KZG_TRUSTED_SETUP)
+                .unwrap()
+                .to_bytes();
+
+        let tx = Transaction::Eip4844(TxEip4844 {
+            chain_id: 1,
+            gas_limit: 21_000,
+            blob_versioned_hashes: vec![kzg_to_versioned_hash(commitment.as_slice())],
+            max_fee_per_blob_gas: 1,
+            ..Default::default()
+        });
+        let sidecar = BlobTransactionSidecar {
+            blobs: vec![data.into()],
+            commitments: vec![(*commitment).into()],
+            proofs: vec![(*proof).into()],
+        };
+        let request = InclusionRequest {
+            tx: TransactionSigned::from_transaction_and_signature(tx, Default::default()),
+            sidecar: Some(sidecar),
+            ..blob_request(0)
+        };
+        (request, commitment, proof)
+    }
+
+    #[test]
+    fn test_kzg_blob_request() {
+        let (request, commitment, proof) = kzg_blob_request();
+        request.validate_blobs().unwrap();
+        let decoded: InclusionRequest =
+            serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+        assert_eq!(decoded, request);
+        decoded.validate_blobs().unwrap();
+
+        // the constraint references the blob by versioned hash, commitment and proof.
+        let constraint = InclusionConstraint::from(request.clone());
+        assert_eq!(constraint.blobs.len(), 1);
+        let versioned_hash = request.tx.blob_versioned_hashes().unwrap()[0];
+        assert_eq!(constraint.blobs[0].versioned_hash.as_bytes(), versioned_hash.as_slice());
+        assert_eq!(constraint.blobs[0].kzg_commitment.to_vec(), commitment.to_vec());
+        assert_eq!(constraint.blobs[0].kzg_proof.to_vec(), proof.to_vec());
+    }
+
+    #[test]
+    fn test_invalid_kzg_proof() {
+        // the commitment is a valid point, but not the proof of the blob.
+        let (mut request, commitment, _) = kzg_blob_request();
+        request.sidecar.as_mut().unwrap().proofs = vec![(*commitment).into()];
+        assert!(matches!(request.validate_blobs(), Err(InclusionRequestError::InvalidSidecar(_))));
+    }
+
+    #[test]
+    fn test_slot_range() {
+        let tx = Transaction::Legacy(TxLegacy::default());
+        let request = InclusionRequest {
+            tx: TransactionSigned::from_transaction_and_signature(tx, Default::default()),
+            sidecar: None,
+            ..blob_request(0)
+        };
+        let ranged = InclusionRequest { last_slot: Some(15), ..request.clone() };
+
+        let json = serde_json::to_value(&ranged).unwrap();
+        assert_eq!(json["last_slot"], 15);
+        assert_eq!(serde_json::from_value::<InclusionRequest>(json).unwrap(), ranged);
+        assert!(serde_json::to_value(&request).unwrap().get("last_slot").is_none());
+
+        // the range is bound into the signed hash.
+        assert_ne!(request.signing_hash(1), ranged.signing_hash(1));
+        assert_ne!(
+            ranged.signing_hash(1),
+            InclusionRequest { last_slot: Some(16), ..ranged.clone() }.signing_hash(1)
+        );
+
+        assert_eq!(ranged.slots(), 10..=15);
+        assert_eq!(request.slots(), 10..=10);
+        ranged.validate_slot_range().unwrap();
+        assert!(matches!(
+            InclusionRequest { last_slot: Some(9), ..request.clone() }.validate_slot_range(),
+            Err(InclusionRequestError::InvalidSlotRange { .. })
+        ));
+        assert!(matches!(
+            InclusionRequest { last_slot: Some(10 + MAX_SLOT_RANGE), ..request }
+                .validate_slot_range(),
+            Err(InclusionRequestError::InvalidSlotRange { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_eip712_signature() {
+        let signer = PrivateKeySigner::from_bytes(&B256::repeat_byte(1)).unwrap();
+        let tx = Transaction::Legacy(TxLegacy::default());
+        let mut request = InclusionRequest {
+            deadline: Some(1_000),
+            tx: TransactionSigned::from_transaction_and_signature(tx, Default::default()),
+            sidecar: None,
+            ..blob_request(0)
+        };
+        request.sign(1, &signer).await.unwrap();
+        assert_eq!(request.verify(1).unwrap(), signer.address());
+
+        // the signature is bound to the chain id and deadline.
+        assert_ne!(request.verify(2).ok(), Some(signer.address()));
+        let extended = InclusionRequest { deadline: Some(2_000), ..request.clone() };
+        assert_ne!(extended.verify(1).ok(), Some(signer.address()));
+
+        // the signature is serialized with `v` as 27/28.
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(
+            json["signature"].as_str().unwrap().ends_with("1b") ||
+                json["signature"].as_str().unwrap().ends_with("1c")
+        );
+        let decoded = serde_json::from_value::<InclusionRequest>(json).unwrap();
+        assert_eq!(decoded.verify(1).unwrap(), signer.address());
+
+        request.validate_deadline(1_000).unwrap();
+        assert!(matches!(
+            request.validate_deadline(1_001),
+            Err(InclusionRequestError::DeadlinePassed { deadline: 1_000 })
+        ));
+    }
+}
@@ -1,10 +1,13 @@
 use alloy::rpc::types::beacon::BlsSignature;
 use serde::Serialize;
-use ssz_types::VariableList;
+use ssz_types::{FixedVector, VariableList};
+use tree_hash::Hash256;
 use tree_hash_derive::TreeHash;
 
-use super::commitments::InclusionRequest;
-use crate::ssz::{MaxTransactionsPerPayload, SszTransaction};
+use super::{bundle::BundleInclusionRequest, commitments::InclusionRequest};
+use crate::ssz::{
+    KzgCommitment, KzgProof, MaxBlobCommitmentsPerBlock, MaxTransactionsPerPayload, SszTransaction,
+};
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct SignedConstraints {
@@ -22,10 +25,17 @@ pub struct ConstraintsMessage {
     pub slot: u64,
     /// All transaction constraints
     /// TODO: we should enum InclusionConstraint
-    pub constraints: VariableList<
-        VariableList<InclusionConstraint, MaxTransactionsPerPayload>,
-        MaxTransactionsPerPayload,
-    >,
+    pub constraints: VariableList<ConstraintGroup, MaxTransactionsPerPayload>,
+}
+
+/// Constraints that must all be included, in order, or not at all.
+pub type ConstraintGroup = VariableList<InclusionConstraint, MaxTransactionsPerPayload>;
+
+impl ConstraintsMessage {
+    /// Appends a group of constraints. It returns false if the message is full.
+    pub fn push_group(&mut self, group: ConstraintGroup) -> bool {
+        self.constraints.push(group).is_ok()
+    }
 }
 
 /// Constraint representing a transaction that must be *included* in a block.
@@ -33,24 +43,77 @@ pub struct ConstraintsMessage {
 pub struct InclusionConstraint {
     #[serde(with = "ssz_types::serde_utils::hex_var_list")]
     pub tx: SszTransaction,
+    /// The blobs of `tx` if it is a blob transaction, empty otherwise.
+    pub blobs: VariableList<BlobReference, MaxBlobCommitmentsPerBlock>,
+}
+
+/// Reference to a blob of a blob transaction constraint, by its versioned hash and KZG
+/// commitment and proof.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, TreeHash)]
+pub struct BlobReference {
+    pub versioned_hash: Hash256,
+    #[serde(with = "ssz_types::serde_utils::hex_fixed_vec")]
+    pub kzg_commitment: KzgCommitment,
+    #[serde(with = "ssz_types::serde_utils::hex_fixed_vec")]
+    pub kzg_proof: KzgProof,
 }
 
 impl From<InclusionRequest> for InclusionConstraint {
     fn from(value: InclusionRequest) -> Self {
         let mut encoded_tx = Vec::new();
         value.tx.encode_enveloped(&mut encoded_tx);
-        Self { tx: encoded_tx.into() }
+
+        let versioned_hashes = value.tx.blob_versioned_hashes().unwrap_or_default();
+        let blobs = match &value.sidecar {
+            Some(sidecar) => versioned_hashes
+                .iter()
+                .zip(sidecar.commitments.iter().zip(sidecar.proofs.iter()))
+                .map(|(versioned_hash, (commitment, proof))| BlobReference {
+                    versioned_hash: Hash256::from_slice(versioned_hash.as_slice()),
+                    kzg_commitment: FixedVector::from(commitment.to_vec()),
+                    kzg_proof: FixedVector::from(proof.to_vec()),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Self { tx: encoded_tx.into(), blobs: blobs.into() }
+    }
+}
+
+impl From<BundleInclusionRequest> for ConstraintGroup {
+    fn from(value: BundleInclusionRequest) -> Self {
+        let constraints = value
+            .txs
+            .iter()
+            .map(|tx| {
+                let mut encoded_tx = Vec::new();
+                tx.encode_enveloped(&mut encoded_tx);
+                InclusionConstraint { tx: encoded_tx.into(), blobs: VariableList::empty() }
+            })
+            .collect::<Vec<_>>();
+        constraints.into()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
+    use alloy::primitives::{FixedBytes, Signature, B256};
+    use reth_primitives::{
+        BlobTransactionSidecar, Transaction, TransactionSigned, TxEip1559, TxEip4844,
+    };
+
     use super::*;
 
     #[test]
     fn test_inclusion_constraint_from_inclusion_request() {
         let constraint = SszTransaction::new(vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
-        let list = VariableList::new(vec![InclusionConstraint { tx: constraint.clone() }]).unwrap();
+        let list = VariableList::new(vec![InclusionConstraint {
+            tx: constraint.clone(),
+            ..Default::default()
+        }])
+        .unwrap();
         let constraints = VariableList::new(vec![list]).unwrap();
 
         let singed_constraints = SignedConstraints {
@@ -61,4 +124,64 @@ mod tests {
         let s = serde_json::to_string(&singed_constraints).unwrap();
         let _encode = alloy::primitives::hex::encode(s);
     }
+
+    #[test]
+    fn test_inclusion_constraint_references_blobs() {
+        let versioned_hash = B256::repeat_byte(1);
+        let tx = Transaction::Eip4844(TxEip4844 {
+            blob_versioned_hashes: vec![versioned_hash],
+            ..Default::default()
+        });
+        let sidecar = BlobTransactionSidecar {
+            blobs: vec![Default::default()],
+            commitments: vec![FixedBytes::repeat_byte(2)],
+            proofs: vec![FixedBytes::repeat_byte(3)],
+        };
+        let request = InclusionRequest {
+            slot: 10,
+            last_slot: None,
+            deadline: None,
+            tx: TransactionSigned::from_transaction_and_signature(tx, Default::default()),
+            sidecar: Some(sidecar),
+            signature: Signature::from_str(&"01".repeat(65)).unwrap(),
+        };
+
+        let constraint = InclusionConstraint::from(request.clone());
+        let mut encoded_tx = Vec::new();
+        request.tx.encode_enveloped(&mut encoded_tx);
+        assert_eq!(constraint.tx.to_vec(), encoded_tx);
+        assert_eq!(constraint.blobs.len(), 1);
+        assert_eq!(constraint.blobs[0].versioned_hash.as_bytes(), versioned_hash.as_slice());
+        assert_eq!(constraint.blobs[0].kzg_commitment.to_vec(), vec![2; 48]);
+        assert_eq!(constraint.blobs[0].kzg_proof.to_vec(), vec![3; 48]);
+    }
+
+    #[test]
+    fn test_constraint_group_from_bundle() {
+        let txs = (0..3)
+            .map(|nonce| {
+                let tx = Transaction::Eip1559(TxEip1559 { nonce, ..Default::default() });
+                TransactionSigned::from_transaction_and_signature(tx, Default::default())
+            })
+            .collect::<Vec<_>>();
+        let bundle = BundleInclusionRequest {
+            slot: 10,
+            txs: txs.clone(),
+            signature: Signature::from_str(&"01".repeat(65)).unwrap(),
+        };
+
+        let mut message = ConstraintsMessage { slot: 10, ..Default::default() };
+        assert!(message.push_group(bundle.into()));
+        assert_eq!(message.constraints.len(), 1);
+
+        // the group keeps the order of the bundle.
+        let group = &message.constraints[0];
+        assert_eq!(group.len(), 3);
+        for (constraint, tx) in group.iter().zip(txs.iter()) {
+            let mut encoded_tx = Vec::new();
+            tx.encode_enveloped(&mut encoded_tx);
+            assert_eq!(constraint.tx.to_vec(), encoded_tx);
+            assert!(constraint.blobs.is_empty());
+        }
+    }
 }
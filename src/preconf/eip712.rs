@@ -0,0 +1,70 @@
+use alloy::{
+    primitives::{B256, U256},
+    sol,
+    sol_types::{Eip712Domain, SolStruct},
+};
+
+use super::{bundle::BundleInclusionRequest, commitments::InclusionRequest};
+
+/// Name of the EIP-712 domain of inclusion requests and bundles.
+pub const DOMAIN_NAME: &str = "preconf-rpc";
+/// Version of the EIP-712 domain of inclusion requests and bundles.
+pub const DOMAIN_VERSION: &str = "1";
+
+sol! {
+    /// EIP-712 typed struct of an inclusion request, as displayed by wallets.
+    /// `lastSlot` equals `slot` for single-slot requests and `deadline` is 0 without deadline.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Inclusion {
+        uint64 slot;
+        uint64 lastSlot;
+        bytes32 txHash;
+        uint64 deadline;
+    }
+
+    /// EIP-712 typed struct of a bundle inclusion request, with the hashes of its transactions
+    /// in order.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Bundle {
+        uint64 slot;
+        bytes32[] txHashes;
+    }
+}
+
+/// Returns the EIP-712 domain of the inclusion requests and bundles of `chain_id`.
+pub fn domain(chain_id: u64) -> Eip712Domain {
+    Eip712Domain::new(
+        Some(DOMAIN_NAME.into()),
+        Some(DOMAIN_VERSION.into()),
+        Some(U256::from(chain_id)),
+        None,
+        None,
+    )
+}
+
+impl From<&InclusionRequest> for Inclusion {
+    fn from(request: &InclusionRequest) -> Self {
+        Self {
+            slot: request.slot,
+            lastSlot: *request.slots().end(),
+            txHash: request.tx.hash(),
+            deadline: request.deadline.unwrap_or_default(),
+        }
+    }
+}
+
+/// Returns the EIP-712 hash of `request` signed by the user for `chain_id`.
+pub fn signing_hash(request: &InclusionRequest, chain_id: u64) -> B256 {
+    Inclusion::from(request).eip712_signing_hash(&domain(chain_id))
+}
+
+impl From<&BundleInclusionRequest> for Bundle {
+    fn from(bundle: &BundleInclusionRequest) -> Self {
+        Self { slot: bundle.slot, txHashes: bundle.txs.iter().map(|tx| tx.hash()).collect() }
+    }
+}
+
+/// Returns the EIP-712 hash of `bundle` signed by the user for `chain_id`.
+pub fn bundle_signing_hash(bundle: &BundleInclusionRequest, chain_id: u64) -> B256 {
+    Bundle::from(bundle).eip712_signing_hash(&domain(chain_id))
+}
@@ -1,6 +1,8 @@
 use alloy::rpc::types::beacon::{BlsPublicKey, BlsSignature};
 use serde::{Deserialize, Serialize};
-use tree_hash_derive::TreeHash;
+use tree_hash::{merkle_root, Hash256, MerkleHasher, PackedEncoding, TreeHash, TreeHashType};
+
+use crate::ssz::optional_tree_hash_root;
 
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct SignedPreconferElection {
@@ -17,9 +19,13 @@ impl SignedPreconferElection {
     pub fn slot(&self) -> u64 {
         self.message.slot_number
     }
+
+    pub fn gas_limit(&self) -> Option<u64> {
+        self.message.gas_limit
+    }
 }
 
-#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, TreeHash)]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct PreconferElection {
     /// Public key of the preconfer for `slot`.
     pub preconfer_pubkey: BlsPublicKey,
@@ -27,7 +33,94 @@ pub struct PreconferElection {
     pub slot_number: u64,
     /// Chain ID this election is valid for. For example `1` for Mainnet.
     pub chain_id: u64,
-    /// Maximum gas used by all pre-confirmations.
-    pub gas_limit: u64, /* TODO: this should be optional but still need to figure out how to
-                         * TreeHash */
+    /// Maximum gas used by all pre-confirmations. `None` if the preconfer did not set a limit.
+    /// Hashed as an SSZ `Optional[uint64]`.
+    #[serde(default)]
+    pub gas_limit: Option<u64>,
+}
+
+impl TreeHash for PreconferElection {
+    fn tree_hash_type() -> TreeHashType {
+        TreeHashType::Container
+    }
+
+    fn tree_hash_packed_encoding(&self) -> PackedEncoding {
+        unreachable!("Container should never be packed.")
+    }
+
+    fn tree_hash_packing_factor() -> usize {
+        unreachable!("Container should never be packed.")
+    }
+
+    fn tree_hash_root(&self) -> Hash256 {
+        let mut hasher = MerkleHasher::with_leaves(4);
+        hasher
+            .write(merkle_root(self.preconfer_pubkey.as_slice(), 0).as_bytes())
+            .expect("should not apply too many leaves");
+        hasher
+            .write(self.slot_number.tree_hash_root().as_bytes())
+            .expect("should not apply too many leaves");
+        hasher
+            .write(self.chain_id.tree_hash_root().as_bytes())
+            .expect("should not apply too many leaves");
+        hasher
+            .write(optional_tree_hash_root(self.gas_limit.as_ref()).as_bytes())
+            .expect("should not apply too many leaves");
+        hasher.finish().expect("should not have a remaining buffer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::rpc::types::beacon::constants::BLS_PUBLIC_KEY_BYTES_LEN;
+
+    use super::*;
+
+    fn election(gas_limit: Option<u64>) -> PreconferElection {
+        PreconferElection {
+            preconfer_pubkey: BlsPublicKey::from([42u8; BLS_PUBLIC_KEY_BYTES_LEN]),
+            slot_number: 1,
+            chain_id: 1,
+            gas_limit,
+        }
+    }
+
+    fn hash256(s: &str) -> Hash256 {
+        Hash256::from_slice(&alloy::hex::decode(s).unwrap())
+    }
+
+    #[test]
+    fn test_tree_hash_root_without_gas_limit() {
+        assert_eq!(
+            election(None).tree_hash_root(),
+            hash256("c8a003550d92f897983c3e87748e3d724607cdfe224b17a0ec64cd2547d2b360")
+        );
+    }
+
+    #[test]
+    fn test_tree_hash_root_with_gas_limit() {
+        assert_eq!(
+            election(Some(30_000_000)).tree_hash_root(),
+            hash256("ff45375d99d365726cbd7d455db35fedf3b3637bdc9bfc30642b29eac47640a9")
+        );
+    }
+
+    #[test]
+    fn test_deserialize_optional_gas_limit() {
+        let pubkey = BlsPublicKey::from([42u8; BLS_PUBLIC_KEY_BYTES_LEN]);
+        let absent = format!(r#"{{"preconfer_pubkey":"{pubkey}","slot_number":1,"chain_id":1}}"#);
+        let null = format!(
+            r#"{{"preconfer_pubkey":"{pubkey}","slot_number":1,"chain_id":1,"gas_limit":null}}"#
+        );
+        let set = format!(
+            r#"{{"preconfer_pubkey":"{pubkey}","slot_number":1,"chain_id":1,"gas_limit":30000000}}"#
+        );
+
+        assert_eq!(serde_json::from_str::<PreconferElection>(&absent).unwrap(), election(None));
+        assert_eq!(serde_json::from_str::<PreconferElection>(&null).unwrap(), election(None));
+        assert_eq!(
+            serde_json::from_str::<PreconferElection>(&set).unwrap(),
+            election(Some(30_000_000))
+        );
+    }
 }
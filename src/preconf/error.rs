@@ -0,0 +1,46 @@
+use reth_primitives::TxHash;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InclusionRequestError {
+    #[error("blob transaction without sidecar")]
+    MissingSidecar,
+
+    #[error("invalid blob sidecar: {0}")]
+    InvalidSidecar(String),
+
+    #[error("invalid inclusion request signature")]
+    InvalidSignature,
+
+    #[error("inclusion request deadline {deadline} has passed")]
+    DeadlinePassed { deadline: u64 },
+
+    #[error("invalid slot range {slot}..={last_slot}, at most {max} slots are allowed")]
+    InvalidSlotRange { slot: u64, last_slot: u64, max: u64 },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("bundle has no transactions")]
+    Empty,
+
+    #[error("bundle has {count} transactions, at most {max} are allowed")]
+    TooManyTransactions { count: usize, max: usize },
+
+    #[error("transaction {0} is included more than once in the bundle")]
+    DuplicateTransaction(TxHash),
+
+    #[error("blob transactions cannot be bundled")]
+    BlobTransaction,
+
+    #[error("bundle transactions have different chain ids")]
+    ChainIdMismatch,
+
+    #[error("bundle transactions are not for chain-id {0}")]
+    WrongChainId(u64),
+
+    #[error("invalid bundle signature")]
+    InvalidSignature,
+
+    #[error("invalid SSZ encoding: {0}")]
+    Ssz(String),
+}
@@ -1,3 +1,6 @@
+pub(crate) mod bundle;
 pub(crate) mod commitments;
 pub(crate) mod constraints;
+pub(crate) mod eip712;
 pub(crate) mod election;
+pub(crate) mod error;
@@ -0,0 +1,282 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use hashbrown::HashMap;
+use http::HeaderMap;
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use crate::constants::{EPOCH_SLOTS, SECONDS_PER_SLOT};
+
+/// Maximum number of requests held in the queue.
+const QUEUE_CAPACITY: usize = 1024;
+/// Queued requests expire if no preconfer is elected for their slot within two epochs.
+const QUEUED_REQUEST_TTL: Duration = Duration::from_secs(2 * EPOCH_SLOTS * SECONDS_PER_SLOT);
+/// Tickets of released or expired requests can be polled for this long.
+const TICKET_RETENTION: Duration = Duration::from_secs(5 * 60);
+
+pub type TicketId = u64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueueError {
+    #[error("request queue is full")]
+    Full,
+}
+
+/// Request held until a preconfer is elected for its slot.
+#[derive(Debug, Clone)]
+pub struct QueuedRequest {
+    pub slot: u64,
+    /// Last slot of the slot range of the request, if any.
+    pub last_slot: Option<u64>,
+    pub gas: u64,
+    pub body: Bytes,
+    pub headers: HeaderMap,
+}
+
+/// Status of a queued request, polled by users with the ticket id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TicketStatus {
+    /// Waiting for a preconfer to be elected for `slot`.
+    Queued {
+        slot: u64,
+    },
+    /// Forwarded to the preconfer elected for `slot`, with the response of the preconfer.
+    Forwarded {
+        slot: u64,
+        response: String,
+    },
+    Failed {
+        error: String,
+    },
+    /// No preconfer was elected for the slot in time.
+    Expired,
+}
+
+#[derive(Debug)]
+struct Ticket {
+    chain_id: u16,
+    status: TicketStatus,
+    updated_at: Instant,
+    /// Notified with the final status of the request.
+    waiters: Vec<oneshot::Sender<TicketStatus>>,
+}
+
+impl Ticket {
+    fn set_status(&mut self, status: TicketStatus, now: Instant) {
+        for waiter in self.waiters.drain(..) {
+            // The waiter may have timed out.
+            let _ = waiter.send(status.clone());
+        }
+        self.status = status;
+        self.updated_at = now;
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Queued requests keyed by chain-id, slot and ticket, so requests are released in order.
+    pending: BTreeMap<(u16, u64, TicketId), (QueuedRequest, Instant)>,
+    tickets: HashMap<TicketId, Ticket>,
+}
+
+/// `RequestQueue` is a bounded in-memory queue holding the requests for slots whose preconfer
+/// is not known yet. Requests are released once a preconfer is elected for their slot, and
+/// their senders can wait for them to be forwarded or poll their ticket.
+#[derive(Debug)]
+pub struct RequestQueue {
+    capacity: usize,
+    next_ticket: AtomicU64,
+    state: Mutex<QueueState>,
+}
+
+impl Default for RequestQueue {
+    fn default() -> Self {
+        Self::new(QUEUE_CAPACITY)
+    }
+}
+
+impl RequestQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, next_ticket: AtomicU64::new(1), state: Default::default() }
+    }
+
+    /// Queues a request and returns its ticket. Fails if the queue is full.
+    pub fn push(&self, chain_id: u16, request: QueuedRequest) -> Result<TicketId, QueueError> {
+        let mut state = self.state.lock().unwrap();
+        if state.pending.len() >= self.capacity {
+            return Err(QueueError::Full);
+        }
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        state.tickets.insert(ticket, Ticket {
+            chain_id,
+            status: TicketStatus::Queued { slot: request.slot },
+            updated_at: now,
+            waiters: Vec::new(),
+        });
+        state.pending.insert((chain_id, request.slot, ticket), (request, now));
+        Ok(ticket)
+    }
+
+    /// Removes and returns the requests of `chain_id` for slots up to `slot`, in slot order.
+    pub fn release(&self, chain_id: u16, slot: u64) -> Vec<(TicketId, QueuedRequest)> {
+        let mut state = self.state.lock().unwrap();
+        let keys: Vec<_> = state
+            .pending
+            .range((chain_id, 0, 0)..=(chain_id, slot, TicketId::MAX))
+            .map(|(key, _)| *key)
+            .collect();
+        keys.into_iter()
+            .filter_map(|key| state.pending.remove(&key).map(|(request, _)| (key.2, request)))
+            .collect()
+    }
+
+    /// Sets the final status of a released request.
+    pub fn complete(&self, ticket: TicketId, status: TicketStatus) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(ticket) = state.tickets.get_mut(&ticket) {
+            ticket.set_status(status, Instant::now());
+        }
+    }
+
+    /// Waits for at most `timeout` for the request of `ticket` to be released and completed.
+    /// Returns its final status, or `None` if it is still queued.
+    pub async fn wait(&self, ticket: TicketId, timeout: Duration) -> Option<TicketStatus> {
+        let status_rx = {
+            let mut state = self.state.lock().unwrap();
+            let ticket = state.tickets.get_mut(&ticket)?;
+            if !matches!(ticket.status, TicketStatus::Queued { .. }) {
+                return Some(ticket.status.clone());
+            }
+            let (status_tx, status_rx) = oneshot::channel();
+            ticket.waiters.push(status_tx);
+            status_rx
+        };
+        tokio::time::timeout(timeout, status_rx).await.ok()?.ok()
+    }
+
+    /// Returns the status of a ticket of `chain_id`.
+    pub fn status(&self, chain_id: u16, ticket: TicketId) -> Option<TicketStatus> {
+        let state = self.state.lock().unwrap();
+        state
+            .tickets
+            .get(&ticket)
+            .filter(|ticket| ticket.chain_id == chain_id)
+            .map(|ticket| ticket.status.clone())
+    }
+
+    /// Expires the requests queued for longer than `QUEUED_REQUEST_TTL` and removes the
+    /// tickets completed more than `TICKET_RETENTION` before `now`.
+    pub fn prune(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let expired: Vec<_> = state
+            .pending
+            .iter()
+            .filter(|(_, (_, queued_at))| now.duration_since(*queued_at) > QUEUED_REQUEST_TTL)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            state.pending.remove(&key);
+            if let Some(ticket) = state.tickets.get_mut(&key.2) {
+                ticket.set_status(TicketStatus::Expired, now);
+            }
+        }
+
+        state.tickets.retain(|_, ticket| {
+            matches!(ticket.status, TicketStatus::Queued { .. }) ||
+                now.duration_since(ticket.updated_at) <= TICKET_RETENTION
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(slot: u64) -> QueuedRequest {
+        QueuedRequest {
+            slot,
+            last_slot: None,
+            gas: 21_000,
+            body: Bytes::new(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_release_up_to_slot() {
+        let queue = RequestQueue::default();
+        let t1 = queue.push(1, request(12)).unwrap();
+        let t2 = queue.push(1, request(10)).unwrap();
+        let t3 = queue.push(1, request(14)).unwrap();
+        let other_chain = queue.push(2, request(10)).unwrap();
+
+        let released: Vec<_> = queue.release(1, 12).into_iter().map(|(t, _)| t).collect();
+        assert_eq!(released, vec![t2, t1]);
+        assert!(queue.release(1, 12).is_empty());
+        assert_eq!(queue.status(1, t3), Some(TicketStatus::Queued { slot: 14 }));
+        assert_eq!(queue.status(1, other_chain), None);
+        assert_eq!(queue.status(2, other_chain), Some(TicketStatus::Queued { slot: 10 }));
+    }
+
+    #[test]
+    fn test_queue_is_bounded() {
+        let queue = RequestQueue::new(2);
+        queue.push(1, request(10)).unwrap();
+        queue.push(1, request(11)).unwrap();
+        assert!(matches!(queue.push(1, request(12)), Err(QueueError::Full)));
+
+        queue.release(1, 10);
+        queue.push(1, request(12)).unwrap();
+    }
+
+    #[test]
+    fn test_prune() {
+        let queue = RequestQueue::default();
+        let forwarded = queue.push(1, request(11)).unwrap();
+        assert_eq!(queue.release(1, 11).len(), 1);
+        queue.complete(forwarded, TicketStatus::Forwarded { slot: 11, response: "ok".into() });
+        let expired = queue.push(1, request(20)).unwrap();
+
+        queue.prune(Instant::now());
+        assert_eq!(queue.status(1, expired), Some(TicketStatus::Queued { slot: 20 }));
+        assert!(queue.status(1, forwarded).is_some());
+
+        let now = Instant::now() + QUEUED_REQUEST_TTL + Duration::from_secs(1);
+        queue.prune(now);
+        assert_eq!(queue.status(1, expired), Some(TicketStatus::Expired));
+        assert!(queue.release(1, 20).is_empty());
+        // completed longer than the retention ago.
+        assert_eq!(queue.status(1, forwarded), None);
+
+        queue.prune(now + TICKET_RETENTION + Duration::from_secs(1));
+        assert_eq!(queue.status(1, expired), None);
+    }
+
+    #[tokio::test]
+    async fn test_wait() {
+        let queue = RequestQueue::default();
+        let ticket = queue.push(1, request(10)).unwrap();
+        assert_eq!(queue.wait(ticket, Duration::from_millis(10)).await, None);
+
+        let forwarded = TicketStatus::Forwarded { slot: 10, response: "ok".into() };
+        let (status, ()) = tokio::join!(queue.wait(ticket, Duration::from_secs(1)), async {
+            queue.release(1, 10);
+            queue.complete(ticket, forwarded.clone());
+        });
+        assert_eq!(status, Some(forwarded.clone()));
+        // completed tickets do not wait.
+        assert_eq!(queue.wait(ticket, Duration::ZERO).await, Some(forwarded));
+        assert_eq!(queue.wait(1000, Duration::ZERO).await, None);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::Instant,
+};
+
+use alloy::primitives::Address;
+use hashbrown::HashMap;
+use http::HeaderMap;
+
+use crate::{
+    config::{RateLimit, TokenBucket},
+    metrics::RATE_LIMITED_REQUESTS,
+};
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// `RateLimiter` is a token bucket per key: each key can send `capacity` requests in a burst,
+/// and its bucket is refilled with `refill-per-sec` requests every second.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    config: TokenBucket,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(config: TokenBucket) -> Self {
+        Self { config, buckets: Default::default() }
+    }
+
+    /// Takes a token from the bucket of `key`. Returns false if the bucket is empty.
+    pub fn try_acquire(&self, key: K, now: Instant) -> bool {
+        let capacity = self.config.capacity as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(key).or_insert(Bucket { tokens: capacity, updated_at: now });
+
+        let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.config.refill_per_sec as f64).min(capacity);
+        bucket.updated_at = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Removes the buckets that are full at `now`, they are the same as new buckets.
+    pub fn prune(&self, now: Instant) {
+        let capacity = self.config.capacity as f64;
+        let refill_per_sec = self.config.refill_per_sec as f64;
+        self.buckets.lock().unwrap().retain(|_, bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens + elapsed * refill_per_sec < capacity
+        });
+    }
+}
+
+/// Rate limits of the forwarded requests, by client IP and by transaction sender.
+#[derive(Debug, Default)]
+pub struct RateLimits {
+    trusted_proxies: Vec<IpAddr>,
+    per_ip: Option<RateLimiter<IpAddr>>,
+    per_sender: Option<RateLimiter<Address>>,
+}
+
+impl From<RateLimit> for RateLimits {
+    fn from(config: RateLimit) -> Self {
+        Self {
+            trusted_proxies: config.trusted_proxies,
+            per_ip: config.per_ip.map(RateLimiter::new),
+            per_sender: config.per_sender.map(RateLimiter::new),
+        }
+    }
+}
+
+impl RateLimits {
+    /// Returns the IP of the client of a request received from `peer`. If `peer` is a trusted
+    /// proxy, the client is the last address of the `X-Forwarded-For` header that is not a
+    /// trusted proxy.
+    pub fn client_ip(&self, peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+        let peer = peer.ip();
+        if !self.trusted_proxies.contains(&peer) {
+            return peer;
+        }
+        headers
+            .get_all(FORWARDED_FOR_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+            .rev()
+            .find(|ip| !self.trusted_proxies.contains(ip))
+            .unwrap_or(peer)
+    }
+
+    /// Returns false if the client at `ip` exceeded its rate limit.
+    pub fn check_ip(&self, ip: IpAddr, now: Instant) -> bool {
+        Self::check(self.per_ip.as_ref(), ip, now, "ip")
+    }
+
+    /// Returns false if `sender` exceeded its rate limit.
+    pub fn check_sender(&self, sender: Address, now: Instant) -> bool {
+        Self::check(self.per_sender.as_ref(), sender, now, "sender")
+    }
+
+    pub fn has_sender_limit(&self) -> bool {
+        self.per_sender.is_some()
+    }
+
+    pub fn prune(&self, now: Instant) {
+        if let Some(limiter) = &self.per_ip {
+            limiter.prune(now);
+        }
+        if let Some(limiter) = &self.per_sender {
+            limiter.prune(now);
+        }
+    }
+
+    fn check<K: Hash + Eq>(
+        limiter: Option<&RateLimiter<K>>,
+        key: K,
+        now: Instant,
+        limit: &str,
+    ) -> bool {
+        let allowed = limiter.map_or(true, |limiter| limiter.try_acquire(key, now));
+        if !allowed {
+            RATE_LIMITED_REQUESTS.with_label_values(&[limit]).inc();
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(TokenBucket { capacity: 2, refill_per_sec: 1 });
+        let now = Instant::now();
+        assert!(limiter.try_acquire(1, now));
+        assert!(limiter.try_acquire(1, now));
+        assert!(!limiter.try_acquire(1, now));
+        assert!(limiter.try_acquire(2, now), "buckets are per key");
+
+        assert!(limiter.try_acquire(1, now + Duration::from_secs(1)));
+        assert!(!limiter.try_acquire(1, now + Duration::from_secs(1)));
+
+        // both buckets are full again, they are removed.
+        limiter.prune(now + Duration::from_secs(3));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 0);
+        limiter.try_acquire(2, now + Duration::from_secs(3));
+        limiter.prune(now + Duration::from_secs(3));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let limits = RateLimits::from(RateLimit {
+            trusted_proxies: vec![proxy, "10.0.0.2".parse().unwrap()],
+            per_ip: None,
+            per_sender: None,
+        });
+        let mut headers = HeaderMap::new();
+        headers
+            .insert(FORWARDED_FOR_HEADER, HeaderValue::from_static("1.1.1.1, 2.2.2.2, 10.0.0.2"));
+
+        // the header is only honored from trusted proxies.
+        let client: SocketAddr = "3.3.3.3:1000".parse().unwrap();
+        assert_eq!(limits.client_ip(client, &headers), client.ip());
+        let peer = SocketAddr::new(proxy, 1000);
+        assert_eq!(limits.client_ip(peer, &headers), "2.2.2.2".parse::<IpAddr>().unwrap());
+        assert_eq!(limits.client_ip(peer, &HeaderMap::new()), proxy);
+    }
+}
@@ -1,61 +1,111 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use futures_util::future::join_all;
+use futures_util::{future::join_all, StreamExt};
 use reqwest::{ClientBuilder, StatusCode};
-use tracing::{error, trace};
+use reqwest_eventsource::EventSource;
+use tokio::{sync::broadcast::Sender, time::sleep};
+use tracing::{debug, error, trace, warn};
 
-use super::RelayClientConfig;
+use super::{
+    health::{RelayHealth, RelayHealthSnapshot},
+    RelayClientConfig,
+};
 use crate::{
-    constants::{EPOCH_SLOTS, GET_PRECONFERS_PATH, GET_PRECONFER_PATH},
+    constants::{EPOCH_SLOTS, GET_PRECONFERS_PATH, GET_PRECONFER_PATH, SUBSCRIBE_PRECONFERS_PATH},
+    metrics::{RELAY_CIRCUIT_OPEN, RELAY_LATENCY, RELAY_REQUESTS},
     preconf::election::SignedPreconferElection,
     relay_client::error::RelayClientError,
 };
 
-const RELAY_CLIENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
-
 /// `RelayClient` handles communication with a single relay.
 #[derive(Clone, Debug)]
 pub struct RelayClient {
     client: reqwest::Client,
     config: Arc<RelayClientConfig>,
+    /// Shared by all clones of the client.
+    health: Arc<RelayHealth>,
 }
 
 impl RelayClient {
     /// Creates a new `RelayClient` instance.
-    /// Initialises a reqwest Client with a 5-second timeout.
+    /// Initialises a reqwest Client with the timeout and default headers of the relay config.
     pub fn new(config: Arc<RelayClientConfig>) -> Self {
-        let client = ClientBuilder::new().timeout(RELAY_CLIENT_REQUEST_TIMEOUT).build().unwrap();
-        Self { client, config }
+        let client = ClientBuilder::new()
+            .timeout(config.timeout)
+            .default_headers(config.headers.clone())
+            .build()
+            .unwrap();
+        Self { client, config, health: Default::default() }
     }
 
     /// Fetches elected preconfers for the entire epoch.
     ///
-    /// If the relay supports lookahead, it uses it to fetch all preconfers at once.
-    /// Otherwise, it fetches preconfers for each individual slot in the epoch.
+    /// If the relay supports lookahead, it uses it to fetch all preconfers at once and fetches
+    /// the slots of the epoch missing from the lookahead individually. Otherwise, it fetches
+    /// preconfers for each individual slot in the epoch. Only elections for `epoch` are returned.
+    ///
+    /// The relay is not queried while its circuit breaker is open.
     pub async fn get_elected_preconfers_for_epoch(
         &self,
         epoch: u64,
     ) -> Result<Option<Vec<SignedPreconferElection>>, RelayClientError> {
-        if self.config.get_lookahead_enabled {
-            return self.get_elected_preconfer_lookahead().await;
+        if !self.health.is_available() {
+            return Err(RelayClientError::CircuitOpen);
         }
 
-        // Fetch the preconfer for each individual slot in the 32 slot epoch.
+        let start = Instant::now();
+        let result = self.fetch_elected_preconfers_for_epoch(epoch).await;
+        self.record_result(&result, start.elapsed());
+        result
+    }
+
+    async fn fetch_elected_preconfers_for_epoch(
+        &self,
+        epoch: u64,
+    ) -> Result<Option<Vec<SignedPreconferElection>>, RelayClientError> {
         let epoch_start_slot = epoch * EPOCH_SLOTS;
-        let mut slot_handles = Vec::new();
-        for i in 0..EPOCH_SLOTS {
-            slot_handles.push(self.get_elected_preconfer_for_slot(epoch_start_slot + i));
+        let epoch_slots = epoch_start_slot..epoch_start_slot + EPOCH_SLOTS;
+
+        let mut preconfer_elections = Vec::new();
+        if self.config.get_lookahead_enabled {
+            // The lookahead contains all elections from the current slot onwards.
+            preconfer_elections = self.get_elected_preconfer_lookahead().await?.unwrap_or_default();
+            preconfer_elections.retain(|election| epoch_slots.contains(&election.slot()));
         }
 
-        let preconfer_elections: Vec<SignedPreconferElection> = join_all(slot_handles)
-            .await
-            .into_iter()
-            .filter_map(|result| result.ok().flatten())
+        // Fetch the preconfer for each individual slot of the 32 slot epoch missing from the
+        // lookahead.
+        let missing_slots: Vec<u64> = epoch_slots
+            .filter(|slot| !preconfer_elections.iter().any(|election| election.slot() == *slot))
             .collect();
+        if !missing_slots.is_empty() {
+            trace!(target: "lookahead", epoch, missing = missing_slots.len(), "fetching missing slots from relay");
+
+            let mut slot_handles = Vec::with_capacity(missing_slots.len());
+            for slot in missing_slots.iter() {
+                slot_handles.push(self.get_elected_preconfer_for_slot(*slot));
+            }
+
+            let mut results = join_all(slot_handles).await;
+            // The relay is considered failing only if nothing could be fetched.
+            if preconfer_elections.is_empty() && results.iter().all(|result| result.is_err()) {
+                if let Some(Err(err)) = results.pop() {
+                    return Err(err);
+                }
+            }
+
+            preconfer_elections.extend(results.into_iter().zip(missing_slots).filter_map(
+                |(result, slot)| result.ok().flatten().filter(|election| election.slot() == slot),
+            ));
+        }
 
         if preconfer_elections.is_empty() {
             Ok(None)
         } else {
+            preconfer_elections.sort_by_key(SignedPreconferElection::slot);
             Ok(Some(preconfer_elections))
         }
     }
@@ -107,8 +157,173 @@ impl RelayClient {
         Ok(Some(preconfer_election))
     }
 
+    /// Subscribes to the SSE stream of new preconfer elections of the relay and sends every
+    /// election to `chan`, with the weight of the relay. Reconnects when the stream ends.
+    pub async fn subscribe_to_elections(&self, chan: Sender<(u32, SignedPreconferElection)>) {
+        let url = format!("{}{}", self.url(), SUBSCRIBE_PRECONFERS_PATH);
+
+        loop {
+            // The client timeout would terminate the stream, so a client without timeout is used.
+            let request = reqwest::Client::new().get(&url).headers(self.config.headers.clone());
+            let mut es = match EventSource::new(request) {
+                Ok(es) => es,
+                Err(err) => {
+                    error!(target: "lookahead", url, err=%err, "could not subscribe to relay elections");
+                    return;
+                }
+            };
+
+            while let Some(event) = es.next().await {
+                match event {
+                    Ok(reqwest_eventsource::Event::Message(message)) => {
+                        match serde_json::from_str::<SignedPreconferElection>(&message.data) {
+                            Ok(election) => {
+                                if chan.send((self.weight(), election)).is_err() {
+                                    debug!("no subscribers connected to relay elections");
+                                }
+                            }
+                            Err(err) => {
+                                error!(target: "lookahead", err=%err, "Error parsing election")
+                            }
+                        }
+                    }
+                    Ok(reqwest_eventsource::Event::Open) => {
+                        debug!(target: "lookahead", url, "subscribed to relay elections");
+                    }
+                    Err(err) => {
+                        warn!(target: "lookahead", url, err=%err, "relay election stream ended, reconnecting...");
+                        es.close();
+                        break;
+                    }
+                }
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Returns true if the relay streams new preconfer elections.
+    pub fn subscribe_enabled(&self) -> bool {
+        self.config.subscribe_enabled
+    }
+
     /// Returns the URL of the relay.
     pub fn url(&self) -> &str {
         &self.config.url
     }
+
+    /// Returns the name of the relay, labeling its metrics.
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Returns the weight of the relay.
+    pub fn weight(&self) -> u32 {
+        self.config.weight
+    }
+
+    /// Returns the current health of the relay.
+    pub fn health(&self) -> RelayHealthSnapshot {
+        self.health.snapshot(self.url())
+    }
+
+    /// Updates the health and metrics of the relay with the result of a request.
+    fn record_result<T>(&self, result: &Result<T, RelayClientError>, latency: Duration) {
+        let relay = self.name();
+        match result {
+            Ok(_) => {
+                self.health.record_success(latency);
+                RELAY_REQUESTS.with_label_values(&[relay, "success"]).inc();
+                RELAY_LATENCY.with_label_values(&[relay]).observe(latency.as_secs_f64());
+                RELAY_CIRCUIT_OPEN.with_label_values(&[relay]).set(0);
+            }
+            Err(error) => {
+                RELAY_REQUESTS.with_label_values(&[relay, "failure"]).inc();
+                if let Some(backoff) = self.health.record_failure() {
+                    warn!(target: "lookahead", relay, ?error, ?backoff, "relay circuit breaker opened");
+                    RELAY_CIRCUIT_OPEN.with_label_values(&[relay]).set(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use axum::{
+        extract::Path,
+        response::{
+            sse::{Event, Sse},
+            IntoResponse,
+        },
+        routing::get,
+        Json, Router,
+    };
+    use futures::stream;
+    use tokio::sync::broadcast;
+
+    use super::*;
+    use crate::{config::Relay, preconf::election::PreconferElection};
+
+    /// Serves `router` on a free port and returns its url.
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        url
+    }
+
+    fn election(slot_number: u64) -> SignedPreconferElection {
+        SignedPreconferElection {
+            message: PreconferElection { slot_number, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fill_missing_slots_of_lookahead() {
+        let router = Router::new()
+            .route(
+                GET_PRECONFERS_PATH,
+                get(|| async { Json(vec![election(32), election(33), election(100)]) }),
+            )
+            .route(
+                &format!("{GET_PRECONFER_PATH}:slot"),
+                get(|Path(slot): Path<u64>| async move {
+                    match slot {
+                        40 => Json(election(40)).into_response(),
+                        _ => StatusCode::NO_CONTENT.into_response(),
+                    }
+                }),
+            );
+        let relay = RelayClient::new(Arc::new(Relay::from_url(serve(router).await).into()));
+        let elections = relay.get_elected_preconfers_for_epoch(1).await.unwrap().unwrap();
+        let slots: Vec<u64> = elections.iter().map(SignedPreconferElection::slot).collect();
+        assert_eq!(slots, vec![32, 33, 40]);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_elections() {
+        let election = election(42);
+        let data = serde_json::to_string(&election).unwrap();
+        let router = Router::new().route(
+            SUBSCRIBE_PRECONFERS_PATH,
+            get(move || {
+                let data = data.clone();
+                async move {
+                    Sse::new(stream::iter(vec![Ok::<_, Infallible>(Event::default().data(data))]))
+                }
+            }),
+        );
+        let relay = RelayClient::new(Arc::new(Relay::from_url(serve(router).await).into()));
+        let weight = relay.weight();
+        let (tx, mut rx) = broadcast::channel(16);
+        tokio::spawn(async move { relay.subscribe_to_elections(tx).await });
+
+        let received = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap();
+        assert_eq!(received.unwrap(), (weight, election));
+    }
 }
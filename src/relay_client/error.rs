@@ -2,4 +2,7 @@
 pub enum RelayClientError {
     #[error("Reqwest error: {0}")]
     ReqwestError(#[from] reqwest::Error),
+
+    #[error("relay circuit breaker is open")]
+    CircuitOpen,
 }
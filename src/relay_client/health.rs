@@ -0,0 +1,145 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// Number of consecutive failures after which the circuit is opened.
+const FAILURE_THRESHOLD: u32 = 3;
+/// Time the circuit stays open after reaching the failure threshold. Doubled on every
+/// subsequent failure, up to `MAX_BACKOFF`.
+const BASE_BACKOFF: Duration = Duration::from_secs(12);
+const MAX_BACKOFF: Duration = Duration::from_secs(384);
+/// Weight of the latest sample in the latency moving average.
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+#[derive(Debug, Default)]
+struct HealthState {
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    /// Exponentially weighted moving average of successful request latencies.
+    latency_ms: Option<f64>,
+    /// The circuit is open, i.e. requests are not sent to the relay, until this instant.
+    open_until: Option<Instant>,
+}
+
+/// `RelayHealth` tracks the success rate and latency of the requests sent to a relay and acts
+/// as a circuit breaker: after `FAILURE_THRESHOLD` consecutive failures, the relay is not
+/// queried until an exponentially increasing backoff has elapsed. Once elapsed, the next
+/// request decides whether the circuit is closed again or stays open for longer.
+#[derive(Debug, Default)]
+pub struct RelayHealth {
+    state: Mutex<HealthState>,
+}
+
+/// Point-in-time view of the health of a relay.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RelayHealthSnapshot {
+    pub url: String,
+    pub successes: u64,
+    pub failures: u64,
+    pub success_rate: Option<f64>,
+    pub latency_ms: Option<f64>,
+    pub circuit_open: bool,
+}
+
+impl RelayHealth {
+    /// Returns true if requests can be sent to the relay, i.e. the circuit is not open.
+    pub fn is_available(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.open_until.is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    /// Records a successful request and closes the circuit.
+    pub fn record_success(&self, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        state.successes += 1;
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.latency_ms = Some(match state.latency_ms {
+            None => latency_ms,
+            Some(avg) => avg + LATENCY_EWMA_ALPHA * (latency_ms - avg),
+        });
+    }
+
+    /// Records a failed request. Returns the backoff if the circuit has been opened.
+    pub fn record_failure(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        state.consecutive_failures += 1;
+        if state.consecutive_failures < FAILURE_THRESHOLD {
+            return None;
+        }
+
+        let exponent = (state.consecutive_failures - FAILURE_THRESHOLD).min(31);
+        let backoff = BASE_BACKOFF.saturating_mul(1 << exponent).min(MAX_BACKOFF);
+        state.open_until = Some(Instant::now() + backoff);
+        Some(backoff)
+    }
+
+    pub fn snapshot(&self, url: &str) -> RelayHealthSnapshot {
+        let state = self.state.lock().unwrap();
+        let total = state.successes + state.failures;
+        RelayHealthSnapshot {
+            url: url.to_string(),
+            successes: state.successes,
+            failures: state.failures,
+            success_rate: (total > 0).then(|| state.successes as f64 / total as f64),
+            latency_ms: state.latency_ms,
+            circuit_open: state.open_until.is_some_and(|open_until| Instant::now() < open_until),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_opens_after_consecutive_failures() {
+        let health = RelayHealth::default();
+        assert_eq!(health.record_failure(), None);
+        assert_eq!(health.record_failure(), None);
+        assert!(health.is_available());
+
+        assert_eq!(health.record_failure(), Some(BASE_BACKOFF));
+        assert!(!health.is_available());
+        assert_eq!(health.record_failure(), Some(BASE_BACKOFF * 2));
+        assert_eq!(health.record_failure(), Some(BASE_BACKOFF * 4));
+
+        for _ in 0..32 {
+            health.record_failure();
+        }
+        assert_eq!(health.record_failure(), Some(MAX_BACKOFF));
+    }
+
+    #[test]
+    fn test_success_closes_circuit() {
+        let health = RelayHealth::default();
+        for _ in 0..FAILURE_THRESHOLD {
+            health.record_failure();
+        }
+        assert!(health.snapshot("relay").circuit_open);
+
+        health.record_success(Duration::from_millis(100));
+        assert!(health.is_available());
+
+        let snapshot = health.snapshot("relay");
+        assert!(!snapshot.circuit_open);
+        assert_eq!(snapshot.successes, 1);
+        assert_eq!(snapshot.failures, 3);
+        assert_eq!(snapshot.success_rate, Some(0.25));
+        assert_eq!(snapshot.latency_ms, Some(100.0));
+    }
+
+    #[test]
+    fn test_latency_moving_average() {
+        let health = RelayHealth::default();
+        health.record_success(Duration::from_millis(100));
+        health.record_success(Duration::from_millis(200));
+        assert_eq!(health.snapshot("relay").latency_ms, Some(120.0));
+    }
+}
@@ -1,18 +1,44 @@
+use std::time::Duration;
+
+use http::HeaderMap;
+
+use crate::config::Relay;
+
 mod client;
 pub(crate) mod error;
+mod health;
 pub(crate) use client::RelayClient;
+pub(crate) use health::RelayHealthSnapshot;
 
 /// Handles communication to a single relay.
 #[derive(Clone, Debug)]
 pub struct RelayClientConfig {
     url: String,
+    /// Name of the relay in metrics.
+    name: String,
     /// True if the relay supports fetching all elected preconfers in 1 call by
     /// leaving out the `slot` query parameter.
     get_lookahead_enabled: bool,
+    /// True if the relay streams new preconfer elections over SSE.
+    subscribe_enabled: bool,
+    /// Timeout of every request sent to the relay.
+    timeout: Duration,
+    /// Headers sent with every request to the relay.
+    headers: HeaderMap,
+    /// Elections from relays with a higher weight take precedence.
+    weight: u32,
 }
 
-impl RelayClientConfig {
-    pub fn new(url: String, get_lookahead_enabled: bool) -> Self {
-        Self { url, get_lookahead_enabled }
+impl From<Relay> for RelayClientConfig {
+    fn from(relay: Relay) -> Self {
+        Self {
+            url: relay.url,
+            name: relay.name,
+            get_lookahead_enabled: relay.get_lookahead_enabled,
+            subscribe_enabled: relay.subscribe_enabled,
+            timeout: Duration::from_millis(relay.timeout_ms),
+            headers: relay.headers,
+            weight: relay.weight,
+        }
     }
 }
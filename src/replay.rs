@@ -0,0 +1,181 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::B256;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use hashbrown::HashMap;
+use http::{HeaderMap, StatusCode};
+
+use crate::constants::{EPOCH_SLOTS, SECONDS_PER_SLOT};
+
+/// Requests are deduplicated for two epochs, as long as they can be held in the request queue.
+const REPLAY_TTL: Duration = Duration::from_secs(2 * EPOCH_SLOTS * SECONDS_PER_SLOT);
+/// Maximum number of requests remembered, the oldest ones are forgotten first.
+const REPLAY_CAPACITY: usize = 16 * 1024;
+
+/// Identifies a verified inclusion request by the hash signed by its user. The signature
+/// itself is left out, any valid signature of the same request is a replay.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReplayKey {
+    chain_id: u16,
+    hash: B256,
+}
+
+impl ReplayKey {
+    pub fn new(chain_id: u16, hash: B256) -> Self {
+        Self { chain_id, hash }
+    }
+}
+
+/// Response to a request, returned as is to its duplicates.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl IntoResponse for CachedResponse {
+    fn into_response(self) -> Response {
+        (self.status, self.headers, self.body).into_response()
+    }
+}
+
+/// Outcome of checking a request against the `ReplayCache`.
+#[derive(Debug, Clone)]
+pub enum Replay {
+    /// First time the request is seen.
+    New,
+    /// The same request is being handled.
+    InFlight,
+    /// The same request was handled with the cached response.
+    Completed(CachedResponse),
+}
+
+/// `ReplayCache` remembers the inclusion requests handled within `REPLAY_TTL`, so the same
+/// signed request is forwarded once and its duplicates are answered with the cached response.
+/// It is bounded to `capacity` requests.
+#[derive(Debug)]
+pub struct ReplayCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<ReplayKey, (Option<CachedResponse>, Instant)>>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        Self::new(REPLAY_TTL, REPLAY_CAPACITY)
+    }
+}
+
+impl ReplayCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self { ttl, capacity, entries: Default::default() }
+    }
+
+    /// Checks whether the request of `key` was already seen within the TTL. New requests are
+    /// recorded as in flight until they are completed or removed, evicting the oldest request
+    /// when the cache is full.
+    pub fn check(&self, key: ReplayKey, now: Instant) -> Replay {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some((_, seen_at)) if now.duration_since(*seen_at) > self.ttl => {}
+            Some((Some(response), _)) => return Replay::Completed(response.clone()),
+            Some((None, _)) => return Replay::InFlight,
+            None => {}
+        }
+        entries.insert(key, (None, now));
+        if entries.len() > self.capacity {
+            let oldest =
+                entries.iter().min_by_key(|(_, (_, seen_at))| *seen_at).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        Replay::New
+    }
+
+    /// Caches the response of the request of `key`.
+    pub fn complete(&self, key: ReplayKey, response: CachedResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some((cached, _)) = entries.get_mut(&key) {
+            *cached = Some(response);
+        }
+    }
+
+    /// Forgets the request of `key`, e.g. when it failed and can be retried.
+    pub fn remove(&self, key: &ReplayKey) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    /// Removes the requests seen more than the TTL before `now`.
+    pub fn prune(&self, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, seen_at)| now.duration_since(*seen_at) <= self.ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> ReplayKey {
+        ReplayKey::new(1, B256::repeat_byte(byte))
+    }
+
+    fn response() -> CachedResponse {
+        CachedResponse { status: StatusCode::OK, headers: HeaderMap::new(), body: "ok".into() }
+    }
+
+    #[test]
+    fn test_deduplicate() {
+        let cache = ReplayCache::new(Duration::from_secs(10), 10);
+        let now = Instant::now();
+
+        assert!(matches!(cache.check(key(10), now), Replay::New));
+        assert!(matches!(cache.check(key(10), now), Replay::InFlight));
+        assert!(matches!(cache.check(key(11), now), Replay::New));
+
+        cache.complete(key(10), response());
+        match cache.check(key(10), now) {
+            Replay::Completed(cached) => assert_eq!(cached.body, "ok"),
+            replay => panic!("unexpected {:?}", replay),
+        }
+
+        // failed requests can be retried.
+        cache.remove(&key(11));
+        assert!(matches!(cache.check(key(11), now), Replay::New));
+    }
+
+    #[test]
+    fn test_expire() {
+        let cache = ReplayCache::new(Duration::from_secs(10), 10);
+        let now = Instant::now();
+        cache.check(key(10), now);
+        cache.complete(key(10), response());
+
+        let later = now + Duration::from_secs(11);
+        assert!(matches!(cache.check(key(10), later), Replay::New));
+
+        cache.prune(later + Duration::from_secs(11));
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_bounded() {
+        let cache = ReplayCache::new(Duration::from_secs(10), 2);
+        let now = Instant::now();
+        cache.check(key(1), now);
+        cache.check(key(2), now + Duration::from_secs(1));
+        cache.check(key(3), now + Duration::from_secs(2));
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+
+        // the oldest request was forgotten.
+        let later = now + Duration::from_secs(3);
+        assert!(matches!(cache.check(key(1), later), Replay::New));
+        assert!(matches!(cache.check(key(3), later), Replay::InFlight));
+    }
+}
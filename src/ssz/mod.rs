@@ -1,9 +1,23 @@
 use ssz_types::{
-    typenum::{U1048576, U1073741824},
-    VariableList,
+    typenum::{U1048576, U1073741824, U4096, U48},
+    FixedVector, VariableList,
 };
+use tree_hash::{mix_in_selector, Hash256, TreeHash};
 
 pub type MaxBytesPerTransaction = U1073741824; // 1,073,741,824
 pub type MaxTransactionsPerPayload = U1048576; // 1,048,576
 
 pub type SszTransaction = VariableList<u8, MaxBytesPerTransaction>;
+
+pub type MaxBlobCommitmentsPerBlock = U4096;
+pub type KzgCommitment = FixedVector<u8, U48>;
+pub type KzgProof = FixedVector<u8, U48>;
+
+/// Returns the tree hash root of an SSZ `Optional[T]`, which is hashed as `Union[None, T]`.
+pub fn optional_tree_hash_root<T: TreeHash>(value: Option<&T>) -> Hash256 {
+    let (root, selector) = match value {
+        None => (Hash256::zero(), 0),
+        Some(value) => (value.tree_hash_root(), 1),
+    };
+    mix_in_selector(&root, selector).expect("selector should be a valid union selector")
+}
@@ -0,0 +1,87 @@
+use alloy::primitives::{Address, U256, U64};
+use reqwest::ClientBuilder;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use url::Url;
+
+use super::error::ValidationError;
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Block {
+    gas_limit: U64,
+}
+
+/// `ExecutionClient` queries the account state and chain parameters transactions are validated
+/// against from an execution node JSON-RPC.
+#[derive(Clone, Debug)]
+pub struct ExecutionClient {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl ExecutionClient {
+    pub fn new(url: Url, timeout: std::time::Duration) -> Self {
+        let client = ClientBuilder::new().timeout(timeout).build().unwrap();
+        Self { client, url }
+    }
+
+    pub async fn chain_id(&self) -> Result<u64, ValidationError> {
+        let chain_id: U64 = self.request("eth_chainId", json!([])).await?;
+        Ok(chain_id.to())
+    }
+
+    /// Returns the nonce of `address` at the latest block.
+    pub async fn nonce(&self, address: Address) -> Result<u64, ValidationError> {
+        let nonce: U64 =
+            self.request("eth_getTransactionCount", json!([address, "latest"])).await?;
+        Ok(nonce.to())
+    }
+
+    /// Returns the balance of `address` at the latest block.
+    pub async fn balance(&self, address: Address) -> Result<U256, ValidationError> {
+        self.request("eth_getBalance", json!([address, "latest"])).await
+    }
+
+    /// Returns the gas limit of the latest block.
+    pub async fn block_gas_limit(&self) -> Result<u64, ValidationError> {
+        let block: Block = self.request("eth_getBlockByNumber", json!(["latest", false])).await?;
+        Ok(block.gas_limit.to())
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, ValidationError> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&request)
+            .send()
+            .await?
+            .json::<JsonRpcResponse<T>>()
+            .await?;
+
+        match (response.result, response.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(error)) => {
+                Err(ValidationError::Rpc(format!("{} ({}): {}", method, error.code, error.message)))
+            }
+            (None, None) => Err(ValidationError::Rpc(format!("{}: empty response", method))),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use alloy::primitives::U256;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ValidationError {
+    #[error("could not recover the transaction sender")]
+    InvalidSignature,
+
+    #[error("transaction chain id {tx} does not match the chain id {expected}")]
+    ChainIdMismatch { tx: u64, expected: u64 },
+
+    #[error("transaction nonce {tx} is lower than the account nonce {account}")]
+    NonceTooLow { tx: u64, account: u64 },
+
+    #[error("account balance {balance} is lower than the maximum transaction cost {cost}")]
+    InsufficientFunds { balance: U256, cost: U256 },
+
+    #[error("transaction gas limit {tx} exceeds the block gas limit {block}")]
+    GasLimitExceeded { tx: u64, block: u64 },
+
+    #[error("execution rpc error: {0}")]
+    Rpc(String),
+
+    #[error("Reqwest error: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+}
+
+impl ValidationError {
+    /// Code returned to users along with the error message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidSignature => "invalid_signature",
+            Self::ChainIdMismatch { .. } => "invalid_chain_id",
+            Self::NonceTooLow { .. } => "nonce_too_low",
+            Self::InsufficientFunds { .. } => "insufficient_funds",
+            Self::GasLimitExceeded { .. } => "gas_limit_exceeded",
+            Self::Rpc(_) | Self::ReqwestError(_) => "execution_rpc_error",
+        }
+    }
+
+    /// Returns true if the transaction is invalid, false if it could not be validated.
+    pub fn is_invalid_transaction(&self) -> bool {
+        !matches!(self, Self::Rpc(_) | Self::ReqwestError(_))
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use alloy::primitives::U256;
+use reth_primitives::TransactionSigned;
+use tokio::sync::OnceCell;
+
+use crate::config::Validation;
+
+mod client;
+pub(crate) mod error;
+
+use client::ExecutionClient;
+use error::ValidationError;
+
+/// `TxValidator` checks the transactions of inclusion requests against the state of an execution
+/// node before they are forwarded, so preconfers do not waste capacity on transactions that
+/// cannot be included.
+#[derive(Debug)]
+pub struct TxValidator {
+    client: ExecutionClient,
+    /// The chain id of the execution node does not change, it is fetched once.
+    chain_id: OnceCell<u64>,
+}
+
+impl From<Validation> for TxValidator {
+    fn from(validation: Validation) -> Self {
+        Self {
+            client: ExecutionClient::new(
+                validation.execution_rpc,
+                Duration::from_millis(validation.timeout_ms),
+            ),
+            chain_id: OnceCell::new(),
+        }
+    }
+}
+
+impl TxValidator {
+    /// Validates the chain id, nonce, gas limit of `tx` and that the balance of its sender covers
+    /// its maximum cost.
+    pub async fn validate(&self, tx: &TransactionSigned) -> Result<(), ValidationError> {
+        let sender = tx.recover_signer().ok_or(ValidationError::InvalidSignature)?;
+
+        let chain_id = *self.chain_id.get_or_try_init(|| self.client.chain_id()).await?;
+        // Legacy transactions without chain id are valid on any chain.
+        if let Some(tx_chain_id) = tx.chain_id() {
+            if tx_chain_id != chain_id {
+                return Err(ValidationError::ChainIdMismatch {
+                    tx: tx_chain_id,
+                    expected: chain_id,
+                });
+            }
+        }
+
+        let (nonce, balance, block_gas_limit) = tokio::try_join!(
+            self.client.nonce(sender),
+            self.client.balance(sender),
+            self.client.block_gas_limit()
+        )?;
+
+        if tx.nonce() < nonce {
+            return Err(ValidationError::NonceTooLow { tx: tx.nonce(), account: nonce });
+        }
+        if tx.gas_limit() > block_gas_limit {
+            return Err(ValidationError::GasLimitExceeded {
+                tx: tx.gas_limit(),
+                block: block_gas_limit,
+            });
+        }
+
+        let cost = max_cost(tx);
+        if balance < cost {
+            return Err(ValidationError::InsufficientFunds { balance, cost });
+        }
+        Ok(())
+    }
+}
+
+/// Maximum cost of `tx`: its value and the gas, including blob gas, at the maximum fees.
+fn max_cost(tx: &TransactionSigned) -> U256 {
+    let gas_cost = U256::from(tx.gas_limit()) * U256::from(tx.max_fee_per_gas());
+    let blob_gas_cost = match (tx.blob_gas_used(), tx.max_fee_per_blob_gas()) {
+        (Some(blob_gas), Some(max_fee)) => U256::from(blob_gas) * U256::from(max_fee),
+        _ => U256::ZERO,
+    };
+    tx.value() + gas_cost + blob_gas_cost
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::B256;
+    use axum::{routing::post, Json, Router};
+    use reth_primitives::{sign_message, Transaction, TxEip1559};
+    use url::Url;
+
+    use super::*;
+
+    /// Spawns an execution node answering the requests of the validator, returns its url.
+    async fn mock_execution_rpc() -> Url {
+        let router = Router::new().route(
+            "/",
+            post(|Json(request): Json<serde_json::Value>| async move {
+                let result = match request["method"].as_str().unwrap() {
+                    "eth_chainId" => serde_json::json!("0x1"),
+                    "eth_getTransactionCount" => serde_json::json!("0x5"),
+                    // 1 ether
+                    "eth_getBalance" => serde_json::json!("0xde0b6b3a7640000"),
+                    "eth_getBlockByNumber" => serde_json::json!({ "gasLimit": "0x1c9c380" }),
+                    _ => serde_json::Value::Null,
+                };
+                Json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        url
+    }
+
+    fn signed_tx(tx: TxEip1559) -> TransactionSigned {
+        let tx = Transaction::Eip1559(tx);
+        let signature = sign_message(B256::repeat_byte(1), tx.signature_hash()).unwrap();
+        TransactionSigned::from_transaction_and_signature(tx, signature)
+    }
+
+    fn valid_tx() -> TxEip1559 {
+        TxEip1559 {
+            chain_id: 1,
+            nonce: 5,
+            gas_limit: 21_000,
+            max_fee_per_gas: 1_000_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let validator = TxValidator::from(Validation {
+            execution_rpc: mock_execution_rpc().await,
+            timeout_ms: Validation::DEFAULT_TIMEOUT_MS,
+        });
+
+        validator.validate(&signed_tx(valid_tx())).await.unwrap();
+
+        let err = validator
+            .validate(&signed_tx(TxEip1559 { chain_id: 2, ..valid_tx() }))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ValidationError::ChainIdMismatch { tx: 2, expected: 1 }));
+
+        let err =
+            validator.validate(&signed_tx(TxEip1559 { nonce: 4, ..valid_tx() })).await.unwrap_err();
+        assert!(matches!(err, ValidationError::NonceTooLow { tx: 4, account: 5 }));
+
+        let err = validator
+            .validate(&signed_tx(TxEip1559 { gas_limit: 30_000_001, ..valid_tx() }))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ValidationError::GasLimitExceeded { block: 30_000_000, .. }));
+
+        let err = validator
+            .validate(&signed_tx(TxEip1559 {
+                value: U256::from(1_000_000_000_000_000_000u128),
+                ..valid_tx()
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "insufficient_funds");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_execution_rpc() {
+        // nothing listens on the port once the listener is dropped.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let execution_rpc =
+            Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+        let validator = TxValidator::from(Validation {
+            execution_rpc,
+            timeout_ms: Validation::DEFAULT_TIMEOUT_MS,
+        });
+        let err = validator.validate(&signed_tx(valid_tx())).await.unwrap_err();
+        assert!(!err.is_invalid_transaction());
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Instant,
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Path, State,
+    },
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use futures::{SinkExt, StreamExt};
+use hashbrown::HashMap;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+};
+use tracing::{error, warn};
+
+use crate::{
+    auth::API_KEY_HEADER,
+    forward_service::{forward_chain_request, SharedState},
+};
+
+/// Maximum number of messages waiting to be sent to a client. Responses wait for room in the
+/// queue, so requests of a client are not read while it does not read its messages, and
+/// notifications are dropped.
+const OUTBOUND_CAPACITY: usize = 256;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Error of a request rejected by the forwarder or the preconfer.
+const REQUEST_FAILED: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Vec<Value>,
+}
+
+/// Topics clients can subscribe to.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Topic {
+    /// Changes of the lookahead of the chain.
+    Lookahead,
+    /// New head slots.
+    Heads,
+}
+
+/// Upgrades `/ws/:chain_id` to a WebSocket accepting JSON-RPC requests:
+/// - `preconf_sendRequest`: forwards an inclusion request or bundle, as `POST /:chain_id`.
+/// - `subscribe`: notifies the changes of the `lookahead` or the new `heads`.
+/// - `unsubscribe`: cancels a subscription.
+///
+/// The API key is checked on upgrade, and every message counts against the rate limit of the
+/// client IP.
+pub(crate) async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<SharedState>>,
+    Path(chain_id): Path<u16>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Response {
+    if state.manager(chain_id).is_none() {
+        error!(name: "managers.get", "no lookahead provider found for chain-id {}", chain_id);
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("no lookahead provider found for chain-id {}", chain_id),
+        )
+            .into_response();
+    }
+    let api_key =
+        headers.get(API_KEY_HEADER).and_then(|key| key.to_str().ok()).map(ToOwned::to_owned);
+    if let Err(err) = state.api_keys().check(api_key.as_deref(), chain_id) {
+        warn!(name: "api_keys.check", chain_id, "{}", err);
+        return (err.status(), err.to_string()).into_response();
+    }
+    let client_ip =
+        connect_info.map(|ConnectInfo(peer)| state.rate_limits().client_ip(peer, &headers));
+    ws.on_upgrade(move |socket| handle_socket(socket, state, chain_id, api_key, client_ip))
+}
+
+async fn handle_socket(
+    socket: WebSocket,
+    state: Arc<SharedState>,
+    chain_id: u16,
+    api_key: Option<String>,
+    client_ip: Option<IpAddr>,
+) {
+    let (mut sink, mut stream) = socket.split();
+    let (outbound_tx, mut outbound_rx) = mpsc::channel::<Message>(OUTBOUND_CAPACITY);
+    let writer = tokio::spawn(async move {
+        while let Some(message) = outbound_rx.recv().await {
+            if sink.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut connection = Connection {
+        state,
+        chain_id,
+        api_key,
+        client_ip,
+        outbound_tx: outbound_tx.clone(),
+        subscriptions: HashMap::new(),
+        next_subscription: 1,
+    };
+    while let Some(Ok(message)) = stream.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let response = match serde_json::from_str::<RpcRequest>(&text) {
+            Ok(request) => connection.handle(request).await,
+            Err(err) => rpc_error(Value::Null, PARSE_ERROR, err.to_string(), None),
+        };
+        if outbound_tx.send(Message::Text(response.to_string())).await.is_err() {
+            break;
+        }
+    }
+
+    for (_, subscription) in connection.subscriptions.drain() {
+        subscription.abort();
+    }
+    writer.abort();
+}
+
+struct Connection {
+    state: Arc<SharedState>,
+    chain_id: u16,
+    api_key: Option<String>,
+    /// Address of the client, unknown if the service is not served with connect info.
+    client_ip: Option<IpAddr>,
+    outbound_tx: mpsc::Sender<Message>,
+    subscriptions: HashMap<u64, JoinHandle<()>>,
+    next_subscription: u64,
+}
+
+impl Connection {
+    async fn handle(&mut self, request: RpcRequest) -> Value {
+        let RpcRequest { id, method, params } = request;
+        if let Some(ip) = self.client_ip {
+            if !self.state.rate_limits().check_ip(ip, Instant::now()) {
+                warn!(name: "rate_limits.check_ip", %ip, "rate limit exceeded");
+                let status = json!({ "status": StatusCode::TOO_MANY_REQUESTS.as_u16() });
+                return rpc_error(
+                    id,
+                    REQUEST_FAILED,
+                    "rate limit exceeded".to_string(),
+                    Some(status),
+                );
+            }
+        }
+        match method.as_str() {
+            "preconf_sendRequest" => match params.into_iter().next() {
+                Some(request) => self.send_request(id, request).await,
+                None => rpc_error(id, INVALID_PARAMS, "missing request".to_string(), None),
+            },
+            "subscribe" => {
+                let topic = params.into_iter().next().map(serde_json::from_value::<Topic>);
+                match topic {
+                    Some(Ok(topic)) => rpc_result(id, json!(self.subscribe(topic))),
+                    _ => rpc_error(id, INVALID_PARAMS, "unknown topic".to_string(), None),
+                }
+            }
+            "unsubscribe" => {
+                let subscription = params.first().and_then(Value::as_u64);
+                let removed = subscription
+                    .and_then(|subscription| self.subscriptions.remove(&subscription))
+                    .map(|handle| handle.abort())
+                    .is_some();
+                rpc_result(id, json!(removed))
+            }
+            _ => rpc_error(id, METHOD_NOT_FOUND, format!("unknown method {}", method), None),
+        }
+    }
+
+    /// Forwards a request as if it was sent to `POST /:chain_id`.
+    async fn send_request(&self, id: Value, request: Value) -> Value {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let body = request.to_string().into();
+        let response = match forward_chain_request(
+            self.state.clone(),
+            self.chain_id,
+            self.api_key.clone(),
+            headers,
+            body,
+        )
+        .await
+        {
+            Ok(response) | Err(response) => response,
+        };
+
+        let status = response.status();
+        let body = match axum::body::to_bytes(response.into_body(), usize::MAX).await {
+            Ok(body) => body,
+            Err(err) => return rpc_error(id, REQUEST_FAILED, err.to_string(), None),
+        };
+        let body = serde_json::from_slice::<Value>(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+        if status.is_success() {
+            rpc_result(id, body)
+        } else {
+            let message = match &body {
+                Value::String(message) => message.clone(),
+                body => body.to_string(),
+            };
+            rpc_error(id, REQUEST_FAILED, message, Some(json!({ "status": status.as_u16() })))
+        }
+    }
+
+    /// Spawns a task notifying the events of `topic` and returns the id of the subscription.
+    fn subscribe(&mut self, topic: Topic) -> u64 {
+        let subscription = self.next_subscription;
+        self.next_subscription += 1;
+
+        let outbound_tx = self.outbound_tx.clone();
+        let handle = match topic {
+            Topic::Lookahead => {
+                let changes_rx = self
+                    .state
+                    .manager(self.chain_id)
+                    .expect("the chain is checked on upgrade")
+                    .subscribe_changes();
+                tokio::spawn(notify(changes_rx, outbound_tx, subscription, |change| {
+                    Some(serde_json::to_value(change).unwrap_or_default())
+                }))
+            }
+            Topic::Heads => {
+                let head_rx = self.state.subscribe_head_events();
+                // Head events are received from every beacon node, each head slot is only
+                // notified once.
+                let mut last_head_slot = 0;
+                tokio::spawn(notify(head_rx, outbound_tx, subscription, move |head| {
+                    if head.slot <= last_head_slot {
+                        return None;
+                    }
+                    last_head_slot = head.slot;
+                    Some(json!({ "slot": head.slot }))
+                }))
+            }
+        };
+        self.subscriptions.insert(subscription, handle);
+        subscription
+    }
+}
+
+/// Sends the events of `events_rx` to the client until the connection is closed. Events are
+/// skipped if `to_json` returns `None`, and dropped if the client does not read its messages.
+async fn notify<T: Clone>(
+    mut events_rx: broadcast::Receiver<T>,
+    outbound_tx: mpsc::Sender<Message>,
+    subscription: u64,
+    mut to_json: impl FnMut(T) -> Option<Value>,
+) {
+    loop {
+        let event = match events_rx.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!(name: "ws.notify", subscription, skipped, "subscription lagging behind");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Some(result) = to_json(event) else { continue };
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "subscription",
+            "params": { "subscription": subscription, "result": result },
+        });
+        match outbound_tx.try_send(Message::Text(notification.to_string())) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                warn!(name: "ws.notify", subscription, "client is too slow, dropping notification");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => return,
+        }
+    }
+}
+
+fn rpc_result(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn rpc_error(id: Value, code: i64, message: String, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}